@@ -6,11 +6,14 @@ use futures::{
     future::{self, Either},
     Future, Stream,
 };
+use tutanota_client::{range::Range, LoginState, TutanotaClient};
 
 enum Operation {
     CreateDraft,
     CreateFolder,
+    Logout,
     ManageFolders,
+    SendDraft,
     ToggleRead,
     ViewMail,
 }
@@ -20,7 +23,7 @@ fn main() {
     let program = arguments.next().unwrap();
     let quit = || {
         eprintln!(
-            "Usage: {} email_address create_folder|manage_folders|view_mail|toggle_read",
+            "Usage: {} email_address create_draft|create_folder|logout|manage_folders|send_draft|toggle_read|view_mail",
             program
         );
         std::process::exit(1);
@@ -32,84 +35,90 @@ fn main() {
     let operation = match &arguments.next().unwrap() as _ {
         "create_draft" => Operation::CreateDraft,
         "create_folder" => Operation::CreateFolder,
+        "logout" => Operation::Logout,
         "manage_folders" => Operation::ManageFolders,
+        "send_draft" => Operation::SendDraft,
         "toggle_read" => Operation::ToggleRead,
         "view_mail" => Operation::ViewMail,
         _ => quit(),
     };
-    let password = rpassword::prompt_password_stderr("Password: ").unwrap_or_else(|error| {
-        eprintln!("Failed to read password: {}", error);
-        std::process::exit(1);
-    });
-    hyper::rt::run(hyper::rt::lazy(|| {
+    let session_path = std::env::var_os("TUTANOTA_SESSION");
+    let session_state = session_path
+        .as_ref()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|session_state| {
+            serde_json::from_slice::<tutanota_client::SessionState>(&session_state).ok()
+        });
+    let password = match session_state {
+        Some(_) => String::new(),
+        None => rpassword::prompt_password_stderr("Password: ").unwrap_or_else(|error| {
+            eprintln!("Failed to read password: {}", error);
+            std::process::exit(1);
+        }),
+    };
+    let config = std::env::var("TUTANOTA_ORIGIN")
+        .map(tutanota_client::Config::new)
+        .unwrap_or_default();
+    hyper::rt::run(hyper::rt::lazy(move || {
         let https = hyper_tls::HttpsConnector::new(4).unwrap();
         let client = hyper::Client::builder().build::<_, hyper::Body>(https);
-        tutanota_client::salt::fetch_salt(&client, &email_address)
-            .and_then(move |salt| {
-                let user_passphrase_key =
-                    tutanota_client::create_user_passphrase_key(&password, &salt);
-                tutanota_client::session::fetch_session(
-                    &client,
-                    "Rust",
-                    &email_address,
-                    &user_passphrase_key,
-                )
-                .and_then(move |response| {
-                    let access_token = response.access_token;
-                    tutanota_client::user::fetch_user(&client, &access_token, &response.user)
-                        .and_then(move |response| {
-                            // XXX avoid panic
-                            let membership = response
-                                .memberships
-                                .iter()
-                                .find(|membership| membership.group_type == "5")
-                                .unwrap();
-                            // XXX avoid panic
-                            let user_group_key = tutanota_client::decrypt_key(
-                                &user_passphrase_key,
-                                &response.user_group.sym_enc_g_key,
-                            )
-                            .unwrap();
-                            // XXX avoid panic
-                            let mail_group_key = tutanota_client::decrypt_key(
-                                &user_group_key,
-                                &membership.sym_enc_g_key,
-                            )
-                            .unwrap();
-                            tutanota_client::mailboxgrouproot::fetch_mailboxgrouproot(
-                                &client,
-                                &access_token,
-                                &membership.group,
-                            )
-                            .and_then(move |mailbox| {
-                                tutanota_client::mailbox::fetch_mailbox(
-                                    &client,
-                                    &access_token,
-                                    &mailbox,
-                                )
-                                .and_then(move |folders| {
-                                    tutanota_client::mailfolder::fetch_mailfolder(
-                                        &client,
-                                        &access_token,
-                                        &folders,
-                                    )
-                                    .and_then(move |folders| -> Box<dyn Future<Error = _, Item = _> + Send> {
-                                        // XXX avoid panic
-                                        match operation {
-                                            Operation::CreateDraft => Box::new(create_draft(&client, &email_address, &access_token, mail_group_key, user_group_key)),
-                                            Operation::CreateFolder => Box::new(tutanota_client::create_mail_folder::create_mail_folder(&client, &access_token, mail_group_key, tutanota_client::create_key(), &folders[0].id, "Test created!").map(|folder| {
-                                                dbg!(folder);
-                                            })),
-                                            Operation::ManageFolders => Box::new(manage_folders(client, access_token, mail_group_key, &folders[0].sub_folders)),
-                                            Operation::ToggleRead => Box::new(toggle_read(client, access_token, &folders[0].mails)),
-                                            Operation::ViewMail => Box::new(fetch_mails(client, access_token, mail_group_key, &folders[0].mails)),
-                                        }
-                                    })
-                                })
-                            })
-                        })
-                })
+        let persistent = session_path.is_some();
+        let client_future = match session_state {
+            Some(session_state) => Either::A(TutanotaClient::resume(client, config, session_state)),
+            None => Either::B(login(client, config, &email_address, &password, persistent)),
+        };
+        let logout = match operation {
+            Operation::Logout => true,
+            _ => false,
+        };
+        // The saved session is removed only once the server has ended it, so it can still be resumed if logging out fails.
+        let logout_session_path = session_path.clone();
+        client_future
+            .map(move |client| {
+                if let Some(session_path) = session_path {
+                    if !logout {
+                        match client.session_state() {
+                            Err(error) => eprintln!("Failed to save session: {:?}", error),
+                            Ok(session_state) => {
+                                if let Err(error) = std::fs::write(
+                                    session_path,
+                                    serde_json::to_vec(&session_state).unwrap(),
+                                ) {
+                                    eprintln!("Failed to save session: {}", error);
+                                }
+                            }
+                        }
+                    }
+                }
+                client
             })
+            .and_then(
+                move |client| -> Box<dyn Future<Error = _, Item = _> + Send> {
+                    match operation {
+                        Operation::CreateDraft => Box::new(
+                            create_draft(client, "alice@example.com".to_owned(), true).map(
+                                |(_, _, draft)| {
+                                    dbg!(draft);
+                                },
+                            ),
+                        ),
+                        Operation::CreateFolder => Box::new(create_folder(client)),
+                        Operation::Logout => Box::new(client.logout().map(|()| {
+                            if let Some(session_path) = logout_session_path {
+                                if let Err(error) = std::fs::remove_file(session_path) {
+                                    if error.kind() != std::io::ErrorKind::NotFound {
+                                        eprintln!("Failed to remove session: {}", error);
+                                    }
+                                }
+                            }
+                        })),
+                        Operation::ManageFolders => Box::new(manage_folders(client)),
+                        Operation::SendDraft => Box::new(send_draft(client)),
+                        Operation::ToggleRead => Box::new(toggle_read(client)),
+                        Operation::ViewMail => Box::new(fetch_mails(client)),
+                    }
+                },
+            )
             .or_else(|error| {
                 eprintln!("Error: {:#?}", error);
                 match error {
@@ -134,90 +143,165 @@ fn main() {
     }));
 }
 
-fn create_draft<C: 'static + hyper::client::connect::Connect>(
-    client: &hyper::Client<C, hyper::Body>,
+fn login<C: 'static + hyper::client::connect::Connect>(
+    client: hyper::Client<C, hyper::Body>,
+    config: tutanota_client::Config,
     email_address: &str,
-    access_token: &str,
-    mail_group_key: [u8; 16],
-    user_group_key: [u8; 16],
-) -> impl Future<Error = tutanota_client::Error, Item = ()> {
-    let session_key = tutanota_client::create_key();
-    let sub_keys = tutanota_client::SubKeys::new(session_key);
-    tutanota_client::create_draft::create_draft(client, access_token, session_key, mail_group_key, user_group_key, tutanota_client::create_draft::DraftData {
-        added_attachments: &[],
-        bcc_recipients: &[],
-        body_text: tutanota_client::encrypt_with_mac(&sub_keys, b"This is a test message."),
-        cc_recipients: &[],
-        // XXX What's this for?
-        confidential: tutanota_client::encrypt_with_mac(&sub_keys, b"0"),
-        // XXX What's this for?
-        id: "xxxxxx",
-        removed_attachments: &[],
-        reply_tos: &[],
-        sender_mail_address: email_address,
-        sender_name: tutanota_client::encrypt_with_mac(&sub_keys, b"Bob"),
-        subject: tutanota_client::encrypt_with_mac(&sub_keys, b"Hello, World!"),
-        to_recipients: &[
-            tutanota_client::create_draft::Recipient {
-                // XXX What's this for?
-                id: "xxxxxx",
-                mail_address: "alice@example.com",
-                name: tutanota_client::encrypt_with_mac(&sub_keys, b"Alice"),
+    password: &str,
+    persistent: bool,
+) -> impl Future<Error = tutanota_client::Error, Item = TutanotaClient<C>> {
+    TutanotaClient::login(client, config, "Rust", email_address, password, persistent).and_then(
+        |login_state| match login_state {
+            LoginState::LoggedIn(client) => Either::A(future::ok(client)),
+            LoginState::SecondFactorPending(pending) => {
+                eprint!("Authentication code: ");
+                let mut code = String::new();
+                if let Err(error) = std::io::stdin().read_line(&mut code) {
+                    eprintln!("Failed to read authentication code: {}", error);
+                    std::process::exit(1);
+                }
+                Either::B(pending.authenticate_totp(code.trim()))
             }
-        ],
-    }).map(|draft| {
-        dbg!(draft);
+        },
+    )
+}
+
+fn create_draft<C: 'static + hyper::client::connect::Connect>(
+    client: TutanotaClient<C>,
+    recipient: String,
+    attach: bool,
+) -> impl Future<
+    Error = tutanota_client::Error,
+    Item = (
+        TutanotaClient<C>,
+        tutanota_client::Key,
+        tutanota_client::id::MailId,
+    ),
+> {
+    let attachment_future = if attach {
+        Either::A(
+            client
+                .create_attachment("hello.txt", "text/plain", b"Hello, World!")
+                .map(Some),
+        )
+    } else {
+        Either::B(future::ok(None))
+    };
+    attachment_future.and_then(move |attachment| {
+        let encrypted = tutanota_client::create_key().and_then(|session_key| {
+            let sub_keys = tutanota_client::SubKeys::new(session_key);
+            let added_attachments = match &attachment {
+                None => vec![],
+                Some(attachment) => vec![tutanota_client::create_draft::DraftAttachment::new_file(
+                    attachment,
+                    client.mail_group_key(),
+                )?],
+            };
+            Ok((
+                session_key,
+                added_attachments,
+                tutanota_client::encrypt_with_mac(&sub_keys, b"This is a test message.")?,
+                // XXX What's this for?
+                tutanota_client::encrypt_with_mac(&sub_keys, b"0")?,
+                tutanota_client::encrypt_with_mac(&sub_keys, b"Bob")?,
+                tutanota_client::encrypt_with_mac(&sub_keys, b"Hello, World!")?,
+                tutanota_client::encrypt_with_mac(&sub_keys, b"Alice")?,
+            ))
+        });
+        let (
+            session_key,
+            added_attachments,
+            body_text,
+            confidential,
+            sender_name,
+            subject,
+            recipient_name,
+        ) = match encrypted {
+            Err(error) => return Either::A(future::err(error)),
+            Ok(encrypted) => encrypted,
+        };
+        Either::B(
+            client
+                .create_draft(
+                    session_key,
+                    tutanota_client::create_draft::DraftData {
+                        added_attachments: &added_attachments,
+                        bcc_recipients: &[],
+                        body_text,
+                        cc_recipients: &[],
+                        confidential,
+                        // XXX What's this for?
+                        id: "xxxxxx",
+                        removed_attachments: &[],
+                        reply_tos: &[],
+                        sender_mail_address: client.email_address(),
+                        sender_name,
+                        subject,
+                        to_recipients: &[tutanota_client::create_draft::Recipient {
+                            // XXX What's this for?
+                            id: "xxxxxx",
+                            mail_address: &recipient,
+                            name: recipient_name,
+                        }],
+                    },
+                )
+                .map(move |draft| (client, session_key, draft)),
+        )
+    })
+}
+
+fn create_folder<C: 'static + hyper::client::connect::Connect>(
+    client: TutanotaClient<C>,
+) -> impl Future<Error = tutanota_client::Error, Item = ()> {
+    client.inbox().and_then(move |inbox| {
+        client
+            .create_mail_folder(&inbox, "Test created!")
+            .map(|folder| {
+                dbg!(folder);
+            })
     })
 }
 
 fn fetch_mails<C: 'static + hyper::client::connect::Connect>(
-    client: hyper::Client<C, hyper::Body>,
-    access_token: String,
-    mail_group_key: [u8; 16],
-    mails: &str,
+    client: TutanotaClient<C>,
 ) -> impl Future<Error = tutanota_client::Error, Item = ()> {
-    tutanota_client::mail::fetch_mail(&client, &access_token, mails).and_then(move |mails| {
-        for mail in &mails {
-            // XXX avoid panic
-            let session_key =
-                tutanota_client::decrypt_key(&mail_group_key, &mail.owner_enc_session_key).unwrap();
-            let session_sub_keys = tutanota_client::SubKeys::new(session_key);
-            // XXX avoid panic
-            let title =
-                tutanota_client::decrypt_with_mac(&session_sub_keys, &mail.subject).unwrap();
+    client
+        .inbox()
+        .and_then(move |inbox| {
+            client
+                .mails(&inbox, &Range::last(100))
+                .map(|mails| (client, mails))
+        })
+        .and_then(|(client, mails)| {
+            for mail in &mails {
+                // XXX avoid panic
+                let mail = mail.decrypt(client.mail_group_key()).unwrap();
+                println!(
+                    "mail, subject: {:?}, from: {:?} <{}>",
+                    mail.subject, mail.sender.name, mail.sender.address,
+                );
+            }
             // XXX avoid panic
-            println!(
-                "mail, subject: {:?}, from: {:?}",
-                std::str::from_utf8(&title).unwrap(),
-                mail.sender.address,
-            );
-        }
-        // XXX avoid panic
-        let mail = mails.into_iter().next().unwrap();
-        fetch_mail_contents(client, access_token, mail_group_key, mail)
-    })
+            let mail = mails.into_iter().next().unwrap();
+            fetch_mail_contents(client, mail)
+        })
 }
 
 fn fetch_mail_contents<C: 'static + hyper::client::connect::Connect>(
-    client: hyper::Client<C, hyper::Body>,
-    access_token: String,
-    mail_group_key: [u8; 16],
+    client: TutanotaClient<C>,
     mail: tutanota_client::mail::Mail,
 ) -> impl Future<Error = tutanota_client::Error, Item = ()> {
+    let mailbody_future = client.mailbody(&mail);
+    let mail_group_key = client.mail_group_key();
     let attachment_future = match mail.attachments.first() {
         None => Either::A(future::ok(None)),
-        Some(attachment) => {
-            let file_future = tutanota_client::file::fetch_file(&client, &access_token, attachment);
-            let filedata_future =
-                tutanota_client::filedata::fetch_filedata(&client, &access_token, attachment)
-                    .and_then(|response| {
-                        response.concat2().map_err(tutanota_client::Error::Network)
-                    });
-            Either::B(file_future.join(filedata_future).map(Some))
-        }
+        Some(attachment) => Either::B(client.file(attachment).and_then(move |file| {
+            client
+                .filedata_stream(&file)
+                .concat2()
+                .map(|file_data| Some((file, file_data)))
+        })),
     };
-    let mailbody_future =
-        tutanota_client::mailbody::fetch_mailbody(&client, &access_token, &mail.body);
     let session_key = mail.owner_enc_session_key;
     attachment_future
         .join(mailbody_future)
@@ -248,128 +332,116 @@ fn fetch_mail_contents<C: 'static + hyper::client::connect::Connect>(
                     std::str::from_utf8(&name).unwrap(),
                     file.size
                 );
-                // XXX avoid panic
-                let file_data =
-                    tutanota_client::decrypt_with_mac(&session_sub_keys, &file_data).unwrap();
                 println!("file data: {:?}", std::str::from_utf8(&file_data));
             }
         })
 }
 
 fn manage_folders<C: 'static + hyper::client::connect::Connect>(
-    client: hyper::Client<C, hyper::Body>,
-    access_token: String,
-    mail_group_key: [u8; 16],
-    folders: &str,
+    client: TutanotaClient<C>,
 ) -> impl Future<Error = tutanota_client::Error, Item = ()> {
-    tutanota_client::mailfolder::fetch_mailfolder(&client, &access_token, folders).and_then(
-        move |folders| {
-            let mut delete_id = None;
-            let mut move_from_mails = None;
-            let mut move_to_id = None;
+    client
+        .inbox()
+        .and_then(move |inbox| client.sub_folders(&inbox).map(|folders| (client, folders)))
+        .and_then(|(client, folders)| {
+            let mail_group_key = client.mail_group_key();
+            let mut delete_folder = None;
+            let mut move_from_folder = None;
+            let mut move_to_folder = None;
             let mut rename_folder = None;
             for folder in folders {
                 // XXX avoid panic
-                let session_key =
-                    tutanota_client::decrypt_key(&mail_group_key, &folder.owner_enc_session_key)
-                        .unwrap();
-                let session_sub_keys = tutanota_client::SubKeys::new(session_key);
-                // XXX avoid panic
-                let name =
-                    tutanota_client::decrypt_with_mac(&session_sub_keys, &folder.name).unwrap();
-                // XXX avoid panic
-                println!("folder, name: {:?}", std::str::from_utf8(&name).unwrap(),);
-                if name.starts_with(b"Test delete!") {
-                    delete_id.get_or_insert(folder.id);
-                } else if name.starts_with(b"Test move from!") {
-                    move_from_mails.get_or_insert(folder.mails);
-                } else if name.starts_with(b"Test move to!") {
-                    move_to_id.get_or_insert(folder.id);
-                } else if name.starts_with(b"Test rename!") {
+                let name = folder.decrypted_name(mail_group_key).unwrap();
+                println!("folder, name: {:?}", name);
+                if name.starts_with("Test delete!") {
+                    delete_folder.get_or_insert(folder);
+                } else if name.starts_with("Test move from!") {
+                    move_from_folder.get_or_insert(folder);
+                } else if name.starts_with("Test move to!") {
+                    move_to_folder.get_or_insert(folder);
+                } else if name.starts_with("Test rename!") {
                     rename_folder.get_or_insert(folder);
                 }
             }
-            let delete_future = match delete_id {
+            let delete_future = match delete_folder {
                 None => Either::A(future::ok(())),
-                Some(id) => Either::B(tutanota_client::delete_mail_folder::delete_mail_folder(
-                    &client,
-                    &access_token,
-                    &id,
-                )),
+                Some(folder) => Either::B(client.delete_mail_folder(&folder)),
             };
             let rename_future = match rename_folder {
                 None => Either::A(future::ok(())),
-                Some(mut folder) => {
-                    // XXX avoid panic
-                    let session_key = tutanota_client::decrypt_key(
-                        &mail_group_key,
-                        &folder.owner_enc_session_key,
-                    )
-                    .unwrap();
-                    let session_sub_keys = tutanota_client::SubKeys::new(session_key);
-                    folder.name =
-                        tutanota_client::encrypt_with_mac(&session_sub_keys, b"Test renamed!");
-                    Either::B(tutanota_client::update_mail_folder::update_mail_folder(
-                        &client,
-                        &access_token,
-                        &folder,
-                    ))
-                }
+                Some(folder) => Either::B(client.rename_mail_folder(&folder, "Test renamed!")),
             };
-            let move_future = match (move_from_mails, move_to_id) {
-                (Some(move_from_mails), Some(move_to_id)) => Either::A(move_mail(
-                    client,
-                    access_token,
-                    &move_from_mails,
-                    move_to_id,
-                )),
+            let move_future = match (move_from_folder, move_to_folder) {
+                (Some(move_from_folder), Some(move_to_folder)) => {
+                    Either::A(move_mail(client, &move_from_folder, move_to_folder))
+                }
                 _ => Either::B(future::ok(())),
             };
             delete_future
                 .join(move_future)
                 .join(rename_future)
                 .map(|_| ())
-        },
-    )
+        })
 }
 
 fn move_mail<C: 'static + hyper::client::connect::Connect>(
-    client: hyper::Client<C, hyper::Body>,
-    access_token: String,
-    mails: &str,
-    move_to_id: (String, String),
+    client: TutanotaClient<C>,
+    move_from_folder: &tutanota_client::mailfolder::Folder,
+    move_to_folder: tutanota_client::mailfolder::Folder,
 ) -> impl Future<Error = tutanota_client::Error, Item = ()> {
-    tutanota_client::mail::fetch_mail(&client, &access_token, mails).and_then(move |mails| {
-        eprintln!("mails to move: {}", mails.len());
-        if mails.is_empty() {
-            Either::A(future::ok(()))
-        } else {
-            let ids: Vec<_> = mails.iter().map(|mail| &mail.id).collect();
-            // XXX avoid panic
-            Either::B(tutanota_client::move_mail::move_mail(
-                &client,
-                &access_token,
-                &ids,
-                &move_to_id,
-            ))
-        }
-    })
+    client
+        .mail_stream(move_from_folder, Range::first(100))
+        .collect()
+        .and_then(move |mails| {
+            eprintln!("mails to move: {}", mails.len());
+            if mails.is_empty() {
+                Either::A(future::ok(()))
+            } else {
+                let ids: Vec<_> = mails.iter().map(|mail| &mail.id).collect();
+                Either::B(client.move_mail(&ids, &move_to_folder))
+            }
+        })
+}
+
+fn send_draft<C: 'static + hyper::client::connect::Connect>(
+    client: TutanotaClient<C>,
+) -> impl Future<Error = tutanota_client::Error, Item = ()> {
+    let recipient = client.email_address().to_owned();
+    create_draft(client, recipient, false)
+        .and_then(|(client, session_key, draft)| {
+            client
+                .mail(&draft)
+                .map(move |draft| (client, session_key, draft))
+        })
+        .and_then(|(client, session_key, draft)| {
+            client
+                .send_draft(tutanota_client::send_draft::SendDraftData {
+                    attachments: &[],
+                    confidential: true,
+                    draft: &draft,
+                    language: "en",
+                    sender_name: "Bob",
+                    session_key,
+                })
+                .map(|mail| {
+                    dbg!(mail);
+                })
+        })
 }
 
 fn toggle_read<C: 'static + hyper::client::connect::Connect>(
-    client: hyper::Client<C, hyper::Body>,
-    access_token: String,
-    mails: &str,
+    client: TutanotaClient<C>,
 ) -> impl Future<Error = tutanota_client::Error, Item = ()> {
-    tutanota_client::mail::fetch_mail(&client, &access_token, mails).and_then(move |mut mails| {
-        // XXX avoid panic
-        let mail = mails.last_mut().unwrap();
-        mail.unread = match &mail.unread as _ {
-            "0" => "1",
-            "1" => "0",
-            _ => panic!(), // XXX avoid panic
-        }
-        .into();
-        tutanota_client::update_mail::update_mail(&client, &access_token, &mail)
-    })
+    client
+        .inbox()
+        .and_then(move |inbox| {
+            client
+                .mails(&inbox, &Range::last(100))
+                .map(|mails| (client, mails))
+        })
+        .and_then(|(client, mails)| {
+            // XXX avoid panic
+            let mail = mails.last().unwrap();
+            client.set_unread(mail, !mail.unread)
+        })
 }
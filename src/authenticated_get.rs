@@ -13,20 +13,16 @@ pub fn get<C: 'static + hyper::client::connect::Connect>(
     access_token: &str,
     url: &str,
 ) -> impl futures::Future<Error = Error, Item = hyper::Chunk> {
-    let mut request = hyper::Request::new(Default::default());
-    // XXX Don't unwrap, but gracefully return error.
-    request.headers_mut().insert(
-        "accessToken",
-        hyper::header::HeaderValue::from_str(access_token).unwrap(),
+    let request = super::protocol::request(
+        hyper::Method::GET,
+        url,
+        Some(access_token),
+        Default::default(),
     );
-    // XXX Don't unwrap, but gracefully return error.
-    *request.uri_mut() = url.parse().unwrap();
-    client.request(request).then(|result| match result {
-        Err(error) => Either::A(future::err(Error::Network(error))),
-        Ok(response) => {
-            if response.status() != hyper::StatusCode::OK {
-                Either::A(future::err(Error::Status(response)))
-            } else if match response.headers().get(hyper::header::CONTENT_TYPE) {
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
                 None => true,
                 Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
             } {
@@ -34,6 +30,5 @@ pub fn get<C: 'static + hyper::client::connect::Connect>(
             } else {
                 Either::B(response.into_body().concat2().map_err(Error::Network))
             }
-        }
-    })
+        })
 }
@@ -0,0 +1,760 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{
+    create_draft::NewDraftAttachment,
+    id::{FileId, FolderId, FolderListId, GroupId, MailId, UserId},
+    mail::Mail,
+    mailfolder::{Folder, MailFolderType},
+    range::Range,
+    session::ChallengeType,
+    user::GroupType,
+    Config, Error, Key,
+};
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+use serde_derive::{Deserialize, Serialize};
+
+/// The result of logging in, which is either a usable session or a session waiting for a second factor.
+pub enum LoginState<C> {
+    LoggedIn(TutanotaClient<C>),
+    SecondFactorPending(SecondFactorPending<C>),
+}
+
+/// A session that can't be used until it has been authenticated with a second factor.
+pub struct SecondFactorPending<C> {
+    access_key: Option<Key>,
+    access_token: String,
+    challenges: Vec<ChallengeType>,
+    client: hyper::Client<C, hyper::Body>,
+    config: Config,
+    email_address: String,
+    user: UserId,
+    user_passphrase_key: Key,
+}
+
+impl<C: 'static + hyper::client::connect::Connect> SecondFactorPending<C> {
+    /// The kinds of second factors that can be used to authenticate the session.
+    pub fn challenges(&self) -> &[ChallengeType] {
+        &self.challenges
+    }
+
+    /// Authenticates the session with a time-based one-time password and then completes the login.
+    pub fn authenticate_totp(
+        self,
+        code: &str,
+    ) -> impl Future<Error = Error, Item = TutanotaClient<C>> {
+        let session = match super::session::session_id(&self.access_token) {
+            Err(error) => return Either::A(future::err(error)),
+            Ok(session) => session,
+        };
+        Either::B(
+            super::second_factor::authenticate_totp(&self.client, &self.config, &session, code)
+                .and_then(move |()| {
+                    TutanotaClient::open(
+                        self.client,
+                        self.access_key,
+                        self.config,
+                        self.email_address,
+                        self.access_token,
+                        self.user,
+                        self.user_passphrase_key,
+                    )
+                }),
+        )
+    }
+}
+
+/// What is needed to resume a session without the password, which can be serialized to be stored between runs.
+///
+/// The group keys are encrypted with the access key of the session, which is kept by the server and fetched when the session is resumed, so the group keys can't be decrypted after the session has been closed. The access token must be kept secret.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SessionState {
+    pub access_token: String,
+    pub email_address: String,
+    #[serde(with = "super::protocol::base64")]
+    pub encrypted_mail_group_key: Vec<u8>,
+    #[serde(with = "super::protocol::base64")]
+    pub encrypted_user_group_key: Vec<u8>,
+    pub mail_group: GroupId,
+    /// The origin of the server the session belongs to.
+    pub origin: String,
+    pub system_folders: FolderListId,
+    pub user: UserId,
+}
+
+// The access token is left out so it's not revealed in logs.
+impl std::fmt::Debug for SessionState {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter
+            .debug_struct("SessionState")
+            .field("access_token", &"[redacted]")
+            .field("email_address", &self.email_address)
+            .field("encrypted_mail_group_key", &self.encrypted_mail_group_key)
+            .field("encrypted_user_group_key", &self.encrypted_user_group_key)
+            .field("mail_group", &self.mail_group)
+            .field("origin", &self.origin)
+            .field("system_folders", &self.system_folders)
+            .field("user", &self.user)
+            .finish()
+    }
+}
+
+/// A logged in session, holding the access token and the decrypted group keys.
+///
+/// Most methods make a single request using the corresponding function in one of the modules of this crate. The exceptions are `folders`, `sub_folders` and `mail_stream`, which page through a list with a request for each range, `folder_tree`, which does so for each level of folders, `system_folder`, `inbox`, `trash` and `restore`, which fetch all top-level folders to find the system folder, and `move_mail_folder`, which creates folders, moves mails and deletes the original folder.
+pub struct TutanotaClient<C> {
+    access_key: Option<Key>,
+    access_token: String,
+    client: hyper::Client<C, hyper::Body>,
+    close_on_drop: Option<Box<dyn FnOnce() + Send>>,
+    config: Config,
+    email_address: String,
+    mail_group: GroupId,
+    mail_group_key: Key,
+    system_folders: FolderListId,
+    user: UserId,
+    user_group_key: Key,
+}
+
+impl<C: 'static + hyper::client::connect::Connect> TutanotaClient<C> {
+    /// Creates a session by fetching the salt and the session, and unless the session is waiting for a second factor, the user, the mailbox group root and the mailbox.
+    ///
+    /// A persistent session stays valid until it's closed, and its state can be stored with `session_state` to resume it later. Otherwise the session only lasts until it has been unused for a while, and `session_state` fails.
+    pub fn login(
+        client: hyper::Client<C, hyper::Body>,
+        config: Config,
+        client_identifier: &str,
+        email_address: &str,
+        password: &str,
+        persistent: bool,
+    ) -> impl Future<Error = Error, Item = LoginState<C>> {
+        let access_key = if persistent {
+            match super::create_key() {
+                Err(error) => return Either::A(future::err(error)),
+                Ok(access_key) => Some(access_key),
+            }
+        } else {
+            None
+        };
+        let client_identifier = client_identifier.to_owned();
+        let email_address = email_address.to_owned();
+        let password = password.to_owned();
+        Either::B(
+            super::salt::fetch_salt(&client, &config, &email_address).and_then(move |salt| {
+                let user_passphrase_key = match super::derive_user_passphrase_key(
+                    &salt.kdf_type,
+                    &password,
+                    &salt.salt,
+                ) {
+                    Err(error) => return Either::A(future::err(error)),
+                    Ok(user_passphrase_key) => user_passphrase_key,
+                };
+                Either::B(
+                    super::session::fetch_session(
+                        &client,
+                        &config,
+                        access_key,
+                        &client_identifier,
+                        &email_address,
+                        &user_passphrase_key,
+                    )
+                    .and_then(move |session| {
+                        if session.challenges.is_empty() {
+                            Either::A(
+                                Self::open(
+                                    client,
+                                    access_key,
+                                    config,
+                                    email_address,
+                                    session.access_token,
+                                    session.user,
+                                    user_passphrase_key,
+                                )
+                                .map(LoginState::LoggedIn),
+                            )
+                        } else {
+                            Either::B(future::ok(LoginState::SecondFactorPending(
+                                SecondFactorPending {
+                                    access_key,
+                                    access_token: session.access_token,
+                                    challenges: session
+                                        .challenges
+                                        .into_iter()
+                                        .map(|challenge| challenge.challenge_type)
+                                        .collect(),
+                                    client,
+                                    config,
+                                    email_address,
+                                    user: session.user,
+                                    user_passphrase_key,
+                                },
+                            )))
+                        }
+                    }),
+                )
+            }),
+        )
+    }
+
+    // Fetches the user, the mailbox group root and the mailbox of an authenticated session.
+    fn open(
+        client: hyper::Client<C, hyper::Body>,
+        access_key: Option<Key>,
+        config: Config,
+        email_address: String,
+        access_token: String,
+        user: UserId,
+        user_passphrase_key: Key,
+    ) -> impl Future<Error = Error, Item = Self> {
+        super::user::fetch_user(&client, &config, &access_token, &user)
+            .and_then(move |user| -> Result<_, Error> {
+                let user_group_key =
+                    super::decrypt_key(&user_passphrase_key, &user.user_group.sym_enc_g_key)
+                        .ok_or(Error::Decryption("user group key"))?;
+                let membership = user
+                    .memberships
+                    .iter()
+                    .find(|membership| membership.group_type == GroupType::Mail)
+                    .ok_or(Error::Missing("mail group membership"))?;
+                let mail_group_key = super::decrypt_key(&user_group_key, &membership.sym_enc_g_key)
+                    .ok_or(Error::Decryption("mail group key"))?;
+                Ok((membership.group.clone(), mail_group_key, user_group_key))
+            })
+            .and_then(move |(mail_group, mail_group_key, user_group_key)| {
+                super::mailboxgrouproot::fetch_mailboxgrouproot(
+                    &client,
+                    &config,
+                    &access_token,
+                    &mail_group,
+                )
+                .and_then(move |mailbox| {
+                    super::mailbox::fetch_mailbox(&client, &config, &access_token, &mailbox).map(
+                        move |system_folders| TutanotaClient {
+                            access_key,
+                            access_token,
+                            client,
+                            close_on_drop: None,
+                            config,
+                            email_address,
+                            mail_group,
+                            mail_group_key,
+                            system_folders,
+                            user,
+                            user_group_key,
+                        },
+                    )
+                })
+            })
+    }
+
+    /// Resumes a session from its stored state without the password, by fetching the access key of the session.
+    ///
+    /// The origin of the configuration is replaced with the origin the session belongs to.
+    ///
+    /// Fails with `Error::NotAuthenticated` if the session has been closed or has expired.
+    pub fn resume(
+        client: hyper::Client<C, hyper::Body>,
+        config: Config,
+        state: SessionState,
+    ) -> impl Future<Error = Error, Item = Self> {
+        let session = match super::session::session_id(&state.access_token) {
+            Err(error) => return Either::A(future::err(error)),
+            Ok(session) => session,
+        };
+        let config = Config {
+            origin: state.origin.clone(),
+            ..config
+        };
+        Either::B(
+            super::session::fetch_access_key(&client, &config, &state.access_token, &session)
+                .and_then(move |access_key| -> Result<_, Error> {
+                    let mail_group_key =
+                        super::decrypt_key(&access_key, &state.encrypted_mail_group_key)
+                            .ok_or(Error::Decryption("mail group key"))?;
+                    let user_group_key =
+                        super::decrypt_key(&access_key, &state.encrypted_user_group_key)
+                            .ok_or(Error::Decryption("user group key"))?;
+                    Ok(TutanotaClient {
+                        access_key: Some(access_key),
+                        access_token: state.access_token,
+                        client,
+                        close_on_drop: None,
+                        config,
+                        email_address: state.email_address,
+                        mail_group: state.mail_group,
+                        mail_group_key,
+                        system_folders: state.system_folders,
+                        user: state.user,
+                        user_group_key,
+                    })
+                }),
+        )
+    }
+
+    /// Closes the session, so the access token can no longer be used and the session can't be resumed.
+    pub fn logout(mut self) -> impl Future<Error = Error, Item = ()> {
+        self.close_on_drop = None;
+        super::delete_session::delete_session(&self.client, &self.config, &self.access_token)
+    }
+
+    /// Sets whether to close the session when the client is dropped. Defaults to `false`.
+    ///
+    /// The session is closed by spawning a request on the default executor of the current thread, so it's only closed if the client is dropped within a running executor, and any error is ignored. To wait for the session to be closed and see any error, use `logout` instead.
+    pub fn set_close_on_drop(&mut self, close_on_drop: bool) {
+        self.close_on_drop = if close_on_drop {
+            let future = super::delete_session::delete_session(
+                &self.client,
+                &self.config,
+                &self.access_token,
+            );
+            Some(Box::new(move || {
+                use tokio_executor::Executor;
+                let _ = tokio_executor::DefaultExecutor::current()
+                    .spawn(Box::new(future.map_err(|_| ())));
+            }))
+        } else {
+            None
+        };
+    }
+
+    /// The state of the session, which can be stored to resume the session later with `resume`.
+    ///
+    /// Fails with `Error::NotPersistent` if the session was not created as a persistent session.
+    pub fn session_state(&self) -> Result<SessionState, Error> {
+        let access_key = self.access_key.ok_or(Error::NotPersistent)?;
+        Ok(SessionState {
+            access_token: self.access_token.clone(),
+            email_address: self.email_address.clone(),
+            encrypted_mail_group_key: super::encrypt_key(access_key, self.mail_group_key)?,
+            encrypted_user_group_key: super::encrypt_key(access_key, self.user_group_key)?,
+            mail_group: self.mail_group.clone(),
+            origin: self.config.origin.clone(),
+            system_folders: self.system_folders.clone(),
+            user: self.user.clone(),
+        })
+    }
+
+    pub fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    pub fn client(&self) -> &hyper::Client<C, hyper::Body> {
+        &self.client
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn email_address(&self) -> &str {
+        &self.email_address
+    }
+
+    pub fn mail_group(&self) -> &GroupId {
+        &self.mail_group
+    }
+
+    pub fn mail_group_key(&self) -> Key {
+        self.mail_group_key
+    }
+
+    pub fn user(&self) -> &UserId {
+        &self.user
+    }
+
+    pub fn user_group_key(&self) -> Key {
+        self.user_group_key
+    }
+
+    /// Encrypts a file with a new session key and uploads it, so it can be added to a draft with `DraftAttachment::new_file`.
+    pub fn create_attachment(
+        &self,
+        name: &str,
+        mime_type: &str,
+        data: &[u8],
+    ) -> impl Future<Error = Error, Item = NewDraftAttachment> {
+        let encrypted = super::create_key().and_then(|session_key| {
+            let sub_keys = super::SubKeys::new(session_key);
+            Ok((
+                session_key,
+                super::encrypt_with_mac(&sub_keys, data)?,
+                super::encrypt_with_mac(&sub_keys, name.as_bytes())?,
+                super::encrypt_with_mac(&sub_keys, mime_type.as_bytes())?,
+                super::protocol::aggregate_id()?,
+            ))
+        });
+        let (session_key, data, enc_file_name, enc_mime_type, id) = match encrypted {
+            Err(error) => return Either::A(future::err(error)),
+            Ok(encrypted) => encrypted,
+        };
+        Either::B(
+            super::filedata::upload_filedata(
+                &self.client,
+                &self.config,
+                &self.access_token,
+                &self.mail_group,
+                data,
+            )
+            .map(move |file_data| NewDraftAttachment {
+                enc_file_name,
+                enc_mime_type,
+                file_data,
+                id,
+                session_key,
+            }),
+        )
+    }
+
+    pub fn create_draft(
+        &self,
+        session_key: Key,
+        draft_data: super::create_draft::DraftData,
+    ) -> impl Future<Error = Error, Item = MailId> {
+        super::create_draft::create_draft(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            session_key,
+            self.mail_group_key,
+            self.user_group_key,
+            draft_data,
+        )
+    }
+
+    pub fn create_mail_folder(
+        &self,
+        parent_folder: &Folder,
+        name: &str,
+    ) -> impl Future<Error = Error, Item = FolderId> {
+        match super::create_key() {
+            Err(error) => Either::A(future::err(error)),
+            Ok(session_key) => Either::B(super::create_mail_folder::create_mail_folder(
+                &self.client,
+                &self.config,
+                &self.access_token,
+                self.mail_group_key,
+                session_key,
+                &parent_folder.id,
+                name,
+            )),
+        }
+    }
+
+    /// Permanently deletes a draft from the given folder.
+    pub fn delete_draft(
+        &self,
+        draft: &Mail,
+        folder: &Folder,
+    ) -> impl Future<Error = Error, Item = ()> {
+        super::delete_draft::delete_draft(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            &draft.id,
+            &folder.id,
+        )
+    }
+
+    pub fn delete_mail_folder(&self, folder: &Folder) -> impl Future<Error = Error, Item = ()> {
+        super::delete_mail_folder::delete_mail_folder(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            folder,
+        )
+    }
+
+    /// Deletes the folders in a single request, refusing to delete system folders.
+    pub fn delete_mail_folders(
+        &self,
+        folders: &[&Folder],
+    ) -> impl Future<Error = Error, Item = ()> {
+        super::delete_mail_folder::delete_mail_folders(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            folders,
+        )
+    }
+
+    /// Permanently deletes the mails from the given folder. See `delete_mails::delete_mails`.
+    pub fn delete_mails(
+        &self,
+        mails: &[&MailId],
+        folder: &Folder,
+    ) -> impl Future<Error = Error, Item = ()> {
+        super::delete_mails::delete_mails(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            mails,
+            &folder.id,
+        )
+    }
+
+    pub fn file(&self, file: &FileId) -> impl Future<Error = Error, Item = super::file::File> {
+        super::file::fetch_file(&self.client, &self.config, &self.access_token, file)
+    }
+
+    pub fn filedata(&self, file: &FileId) -> impl Future<Error = Error, Item = hyper::Body> {
+        super::filedata::fetch_filedata(&self.client, &self.config, &self.access_token, file)
+    }
+
+    /// Fetches the data of the file and decrypts it as it's received. See `filedata::decrypt_filedata`.
+    pub fn filedata_stream(
+        &self,
+        file: &super::file::File,
+    ) -> impl Stream<Error = Error, Item = Vec<u8>> {
+        let session_key = file.session_key(self.mail_group_key);
+        let filedata = self.filedata(&file.id);
+        future::result(session_key)
+            .and_then(move |session_key| {
+                filedata.map(move |body| super::filedata::decrypt_filedata(body, session_key))
+            })
+            .flatten_stream()
+    }
+
+    /// Fetches all folders of the mailbox as a tree, with their names decrypted.
+    pub fn folder_tree(
+        &self,
+    ) -> impl Future<Error = Error, Item = Vec<super::folder_tree::FolderNode>> {
+        super::folder_tree::fetch_folder_tree(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            self.mail_group_key,
+            &self.system_folders,
+        )
+    }
+
+    /// Fetches the top level folders of the mailbox.
+    pub fn folders(&self) -> impl Future<Error = Error, Item = Vec<Folder>> {
+        super::mailfolder::stream_mailfolder(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            &self.system_folders,
+            Range::first(1000),
+        )
+        .collect()
+    }
+
+    /// Fetches the inbox folder of the mailbox.
+    pub fn inbox(&self) -> impl Future<Error = Error, Item = Folder> {
+        self.system_folder(MailFolderType::Inbox)
+    }
+
+    /// Fetches a single mail. See `mail::fetch_single_mail`.
+    pub fn mail(&self, mail: &MailId) -> impl Future<Error = Error, Item = Mail> {
+        super::mail::fetch_single_mail(&self.client, &self.config, &self.access_token, mail)
+    }
+
+    pub fn mailbody(&self, mail: &Mail) -> impl Future<Error = Error, Item = Vec<u8>> {
+        super::mailbody::fetch_mailbody(&self.client, &self.config, &self.access_token, &mail.body)
+    }
+
+    /// Fetches the mails in the given range of the given folder.
+    pub fn mails(
+        &self,
+        folder: &Folder,
+        range: &Range,
+    ) -> impl Future<Error = Error, Item = Vec<Mail>> {
+        super::mail::fetch_mail(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            &folder.mails,
+            range,
+        )
+    }
+
+    /// Fetches all mails in the given folder from the start of the given range. See `mail::stream_mail`.
+    pub fn mail_stream(
+        &self,
+        folder: &Folder,
+        range: Range,
+    ) -> impl Stream<Error = Error, Item = Mail> {
+        super::mail::stream_mail(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            &folder.mails,
+            range,
+        )
+    }
+
+    /// Moves the folder and its sub folders into the given parent folder, returning the new identifier of the folder. See `move_mail_folder::move_mail_folder`.
+    pub fn move_mail_folder(
+        &self,
+        folder: &super::folder_tree::FolderNode,
+        new_parent: &Folder,
+    ) -> impl Future<Error = Error, Item = FolderId> {
+        super::move_mail_folder::move_mail_folder(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            self.mail_group_key,
+            folder,
+            &new_parent.id,
+        )
+    }
+
+    fn move_to_system_folder(
+        &self,
+        mails: &[&MailId],
+        folder_type: MailFolderType,
+    ) -> impl Future<Error = Error, Item = ()> {
+        let client = self.client.clone();
+        let config = self.config.clone();
+        let access_token = self.access_token.clone();
+        let mails: Vec<MailId> = mails.iter().map(|&mail| mail.clone()).collect();
+        self.system_folder(folder_type).and_then(move |folder| {
+            let mails: Vec<&MailId> = mails.iter().collect();
+            super::move_mail::move_mail(&client, &config, &access_token, &mails, &folder.id)
+        })
+    }
+
+    pub fn move_mail(
+        &self,
+        mails: &[&MailId],
+        target_folder: &Folder,
+    ) -> impl Future<Error = Error, Item = ()> {
+        super::move_mail::move_mail(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            mails,
+            &target_folder.id,
+        )
+    }
+
+    pub fn send_draft(
+        &self,
+        send_draft_data: super::send_draft::SendDraftData,
+    ) -> impl Future<Error = Error, Item = MailId> {
+        super::send_draft::send_draft(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            send_draft_data,
+        )
+    }
+
+    /// Renames the folder. See `rename_mail_folder::rename_mail_folder`.
+    pub fn rename_mail_folder(
+        &self,
+        folder: &Folder,
+        name: &str,
+    ) -> impl Future<Error = Error, Item = ()> {
+        super::rename_mail_folder::rename_mail_folder(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            self.mail_group_key,
+            folder,
+            name,
+        )
+    }
+
+    /// Moves the mails to the inbox folder, typically to restore them from the trash folder.
+    ///
+    /// The folder a mail was in before it was trashed is not known, so all mails are moved to the inbox, including sent mails. To restore mails to another folder, use `move_mail`. Like `system_folder`, this fetches all top-level folders to find the inbox before moving the mails.
+    pub fn restore(&self, mails: &[&MailId]) -> impl Future<Error = Error, Item = ()> {
+        self.move_to_system_folder(mails, MailFolderType::Inbox)
+    }
+
+    /// Marks the mail as unread or read.
+    pub fn set_unread(&self, mail: &Mail, unread: bool) -> impl Future<Error = Error, Item = ()> {
+        self.set_unread_many(&[&mail.id], unread)
+    }
+
+    /// Marks the mails as unread or read in a single request.
+    pub fn set_unread_many(
+        &self,
+        mails: &[&MailId],
+        unread: bool,
+    ) -> impl Future<Error = Error, Item = ()> {
+        super::set_unread::set_unread(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            mails,
+            unread,
+        )
+    }
+
+    /// Fetches the sub folders of the given folder.
+    pub fn sub_folders(&self, folder: &Folder) -> impl Future<Error = Error, Item = Vec<Folder>> {
+        super::mailfolder::stream_mailfolder(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            &folder.sub_folders,
+            Range::first(1000),
+        )
+        .collect()
+    }
+
+    /// Fetches the system folder of the given type, such as the trash folder.
+    ///
+    /// The system folders are not kept by the client, so this fetches all top-level folders each time it's called.
+    pub fn system_folder(
+        &self,
+        folder_type: MailFolderType,
+    ) -> impl Future<Error = Error, Item = Folder> {
+        self.folders().and_then(move |folders| {
+            folders
+                .into_iter()
+                .find(|folder| folder.folder_type == folder_type)
+                .ok_or(Error::Missing("system folder"))
+        })
+    }
+
+    /// Moves the mails to the trash folder.
+    ///
+    /// Like `system_folder`, this fetches all top-level folders to find the trash folder before moving the mails.
+    pub fn trash(&self, mails: &[&MailId]) -> impl Future<Error = Error, Item = ()> {
+        self.move_to_system_folder(mails, MailFolderType::Trash)
+    }
+
+    /// Replaces the contents of a draft. The encrypted values must be encrypted with the session key of the draft, which can be obtained with `Mail::session_key`.
+    pub fn update_draft(
+        &self,
+        draft: &Mail,
+        draft_data: super::create_draft::DraftData,
+    ) -> impl Future<Error = Error, Item = Vec<FileId>> {
+        super::update_draft::update_draft(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            &draft.id,
+            draft_data,
+        )
+    }
+
+    pub fn update_mail(&self, mail: &Mail) -> impl Future<Error = Error, Item = ()> {
+        super::update_mail::update_mail(&self.client, &self.config, &self.access_token, mail)
+    }
+
+    /// Stores changes to a folder. A folder can't be moved to another parent this way, use `move_mail_folder` instead.
+    pub fn update_mail_folder(&self, folder: &Folder) -> impl Future<Error = Error, Item = ()> {
+        super::update_mail_folder::update_mail_folder(
+            &self.client,
+            &self.config,
+            &self.access_token,
+            folder,
+        )
+    }
+}
+
+impl<C> Drop for TutanotaClient<C> {
+    fn drop(&mut self) {
+        if let Some(close) = self.close_on_drop.take() {
+            close();
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+/// Settings shared by all requests to the server.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The scheme, host and optional port of the server, without a trailing slash.
+    ///
+    /// Defaults to `https://mail.tutanota.com`.
+    pub origin: String,
+    /// Whether to fail with `Error::UnknownFields` when an entity in a response has fields not known to this crate, instead of keeping them in the `extra` field of the entity.
+    ///
+    /// Defaults to `false`. It's useful for detecting changes to the data model of the server.
+    pub strict: bool,
+}
+
+impl Config {
+    /// Creates a configuration for the server at the given origin, for example `http://localhost:9000`.
+    pub fn new(origin: impl Into<String>) -> Self {
+        let mut origin = origin.into();
+        while origin.ends_with('/') {
+            origin.pop();
+        }
+        Config {
+            origin,
+            strict: false,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new("https://mail.tutanota.com")
+    }
+}
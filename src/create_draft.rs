@@ -2,18 +2,60 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{
+    file::File,
+    id::{FileDataId, FileId, MailId},
+    Error, Key,
+};
 use futures::{
     future::{self, Either},
     Future, Stream,
 };
 use serde_derive::{Deserialize, Serialize};
 
+/// An attachment added to a draft, either a newly uploaded file or an existing file such as an attachment of a forwarded mail.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DraftAttachment<'a> {
+    pub existing_file: Option<&'a FileId>,
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub new_file: Option<&'a NewDraftAttachment>,
+    /// The session key of the file encrypted with the mail group key.
+    #[serde(with = "super::protocol::base64")]
+    pub owner_enc_file_session_key: Vec<u8>,
+}
+
+impl<'a> DraftAttachment<'a> {
+    /// Adds an existing file, such as an attachment of a forwarded mail, to a draft, giving the file session key to the mail group.
+    pub fn existing_file(file: &'a File, mail_group_key: Key) -> Result<Self, Error> {
+        let session_key = file.session_key(mail_group_key)?;
+        Ok(DraftAttachment {
+            existing_file: Some(&file.id),
+            id: super::protocol::aggregate_id()?,
+            new_file: None,
+            owner_enc_file_session_key: super::encrypt_key(mail_group_key, session_key)?,
+        })
+    }
+
+    /// Adds a file uploaded with `TutanotaClient::create_attachment` to a draft, giving the file session key to the mail group.
+    pub fn new_file(
+        attachment: &'a NewDraftAttachment,
+        mail_group_key: Key,
+    ) -> Result<Self, Error> {
+        Ok(DraftAttachment {
+            existing_file: None,
+            id: super::protocol::aggregate_id()?,
+            new_file: Some(attachment),
+            owner_enc_file_session_key: super::encrypt_key(mail_group_key, attachment.session_key)?,
+        })
+    }
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DraftData<'a> {
-    // XXX What's the proper type?
-    pub added_attachments: &'a [()],
+    pub added_attachments: &'a [DraftAttachment<'a>],
     pub bcc_recipients: &'a [Recipient<'a>],
     #[serde(with = "super::protocol::base64")]
     pub body_text: Vec<u8>,
@@ -22,8 +64,7 @@ pub struct DraftData<'a> {
     pub confidential: Vec<u8>,
     #[serde(rename = "_id")]
     pub id: &'a str,
-    // XXX What's the proper type?
-    pub removed_attachments: &'a [()],
+    pub removed_attachments: &'a [&'a FileId],
     // XXX What's the proper type?
     pub reply_tos: &'a [()],
     pub sender_mail_address: &'a str,
@@ -34,6 +75,22 @@ pub struct DraftData<'a> {
     pub to_recipients: &'a [Recipient<'a>],
 }
 
+/// A file uploaded to be attached to a draft, with its name and MIME type encrypted with the session key of the file.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewDraftAttachment {
+    #[serde(with = "super::protocol::base64")]
+    pub enc_file_name: Vec<u8>,
+    #[serde(with = "super::protocol::base64")]
+    pub enc_mime_type: Vec<u8>,
+    pub file_data: FileDataId,
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// The session key the file data, name and MIME type are encrypted with. It's not sent with the draft.
+    #[serde(skip)]
+    pub session_key: Key,
+}
+
 #[derive(Serialize)]
 pub struct Recipient<'a> {
     #[serde(rename = "_id")]
@@ -63,41 +120,44 @@ struct Request<'a> {
 struct Response {
     #[serde(with = "super::protocol::format")]
     _format: (),
-    draft: (String, String),
+    draft: MailId,
 }
 
 pub fn create_draft<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    session_key: [u8; 16],
-    mail_group_key: [u8; 16],
-    user_group_key: [u8; 16],
+    session_key: Key,
+    mail_group_key: Key,
+    user_group_key: Key,
     draft_data: DraftData,
-) -> impl futures::Future<Error = Error, Item = (String, String)> {
-    let request_body = serde_json::to_string(&Request {
-        conversation_type: (),
-        format: (),
-        draft_data,
-        owner_enc_session_key: base64::encode(&super::encrypt_key(mail_group_key, session_key)[..]),
-        previous_message_id: (),
-        sym_enc_session_key: base64::encode(&super::encrypt_key(user_group_key, session_key)[..]),
-    })
-    .unwrap();
-    let mut request = hyper::Request::new(hyper::Body::from(request_body));
-    *request.method_mut() = hyper::Method::POST;
-    // XXX Don't unwrap, but gracefully return error.
-    request.headers_mut().insert(
-        "accessToken",
-        hyper::header::HeaderValue::from_str(access_token).unwrap(),
-    );
-    *request.uri_mut() =
-        hyper::Uri::from_static("https://mail.tutanota.com/rest/tutanota/draftservice");
-    client.request(request).then(|result| match result {
-        Err(error) => Either::A(future::err(Error::Network(error))),
-        Ok(response) => {
-            if response.status() != hyper::StatusCode::CREATED {
-                Either::A(future::err(Error::Status(response)))
-            } else if match response.headers().get(hyper::header::CONTENT_TYPE) {
+) -> impl futures::Future<Error = Error, Item = MailId> {
+    let url = format!("{}/rest/tutanota/draftservice", config.origin);
+    let request = super::encrypt_key(mail_group_key, session_key)
+        .and_then(|owner_enc_session_key| {
+            let sym_enc_session_key = super::encrypt_key(user_group_key, session_key)?;
+            serde_json::to_string(&Request {
+                conversation_type: (),
+                format: (),
+                draft_data,
+                owner_enc_session_key: base64::encode(&owner_enc_session_key),
+                previous_message_id: (),
+                sym_enc_session_key: base64::encode(&sym_enc_session_key),
+            })
+            .map_err(Error::Serialization)
+        })
+        .and_then(|request_body| {
+            super::protocol::request(
+                hyper::Method::POST,
+                &url,
+                Some(access_token),
+                request_body.into(),
+            )
+        });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
                 None => true,
                 Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
             } {
@@ -111,6 +171,5 @@ pub fn create_draft<C: 'static + hyper::client::connect::Connect>(
                     },
                 }))
             }
-        }
-    })
+        })
 }
@@ -2,7 +2,7 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{id::FolderId, Error, Key};
 use futures::{
     future::{self, Either},
     Future, Stream,
@@ -16,7 +16,7 @@ struct Request<'a> {
     #[serde(rename = "_format")]
     format: &'a str,
     owner_enc_session_key: String,
-    parent_folder: &'a (String, String),
+    parent_folder: &'a FolderId,
 }
 
 #[derive(Deserialize)]
@@ -24,42 +24,42 @@ struct Response {
     #[serde(with = "super::protocol::format")]
     _format: (),
     #[serde(rename = "newFolder")]
-    new_folder: (String, String),
+    new_folder: FolderId,
 }
 
 pub fn create_mail_folder<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    group_key: [u8; 16],
-    session_key: [u8; 16],
-    parent_folder: &(String, String),
+    group_key: Key,
+    session_key: Key,
+    parent_folder: &FolderId,
     name: &str,
-) -> impl futures::Future<Error = Error, Item = String> {
-    let request_body = serde_json::to_string(&Request {
-        folder_name: base64::encode(&super::encrypt_with_mac(
-            &super::SubKeys::new(session_key),
-            name.as_bytes(),
-        )),
-        format: "0",
-        owner_enc_session_key: base64::encode(&super::encrypt_key(group_key, session_key)),
-        parent_folder,
-    })
-    .unwrap();
-    let mut request = hyper::Request::new(hyper::Body::from(request_body));
-    *request.method_mut() = hyper::Method::POST;
-    // XXX Don't unwrap, but gracefully return error.
-    request.headers_mut().insert(
-        "accessToken",
-        hyper::header::HeaderValue::from_str(access_token).unwrap(),
-    );
-    *request.uri_mut() =
-        hyper::Uri::from_static("https://mail.tutanota.com/rest/tutanota/mailfolderservice");
-    client.request(request).then(|result| match result {
-        Err(error) => Either::A(future::err(Error::Network(error))),
-        Ok(response) => {
-            if response.status() != hyper::StatusCode::CREATED {
-                Either::A(future::err(Error::Status(response)))
-            } else if match response.headers().get(hyper::header::CONTENT_TYPE) {
+) -> impl futures::Future<Error = Error, Item = FolderId> {
+    let url = format!("{}/rest/tutanota/mailfolderservice", config.origin);
+    let request = super::encrypt_with_mac(&super::SubKeys::new(session_key), name.as_bytes())
+        .and_then(|folder_name| {
+            let owner_enc_session_key = super::encrypt_key(group_key, session_key)?;
+            serde_json::to_string(&Request {
+                folder_name: base64::encode(&folder_name),
+                format: "0",
+                owner_enc_session_key: base64::encode(&owner_enc_session_key),
+                parent_folder,
+            })
+            .map_err(Error::Serialization)
+        })
+        .and_then(|request_body| {
+            super::protocol::request(
+                hyper::Method::POST,
+                &url,
+                Some(access_token),
+                request_body.into(),
+            )
+        });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
                 None => true,
                 Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
             } {
@@ -69,10 +69,9 @@ pub fn create_mail_folder<C: 'static + hyper::client::connect::Connect>(
                     Err(error) => Err(Error::Network(error)),
                     Ok(response_body) => match serde_json::from_slice::<Response>(&response_body) {
                         Err(error) => Err(Error::Format(error)),
-                        Ok(response_data) => Ok(response_data.new_folder.0),
+                        Ok(response_data) => Ok(response_data.new_folder),
                     },
                 }))
             }
-        }
-    })
+        })
 }
@@ -2,35 +2,186 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
+use super::Error;
 use aes::block_cipher_trait::generic_array::{ArrayLength, GenericArray};
 use sha2::Digest;
 
+const FIXED_IV: [u8; 16] = [0x88; 16];
+const HASH_SIZE: usize = 32;
 const MAC_SIZE: usize = 32;
+const RSA_PUBLIC_EXPONENT: u32 = 65537;
+
+/// A symmetric key, which is either an older 128-bit key or a 256-bit key as created by current clients.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Key {
+    Aes128([u8; 16]),
+    Aes256([u8; 32]),
+}
+
+impl Key {
+    /// Takes a key of 16 or 32 bytes, or returns `None` for any other length.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes.len() {
+            16 => {
+                let mut key = [0; 16];
+                key.copy_from_slice(bytes);
+                Some(Key::Aes128(key))
+            }
+            32 => {
+                let mut key = [0; 32];
+                key.copy_from_slice(bytes);
+                Some(Key::Aes256(key))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Key::Aes128(key) => key,
+            Key::Aes256(key) => key,
+        }
+    }
+}
+
+/// Decrypts a message encrypted with `encrypt_with_mac` piece by piece, holding only a few blocks in memory at a time.
+///
+/// Decrypted data is returned before the MAC at the end of the message has been verified, so it must not be trusted until `finish` has succeeded.
+pub struct Decryptor {
+    buffer: Vec<u8>,
+    cipher: Option<Cbc>,
+    cipher_key: Key,
+    mac: hmac::Hmac<sha2::Sha256>,
+}
+
+impl Decryptor {
+    pub fn new(sub_keys: &SubKeys) -> Self {
+        use hmac::Mac;
+        Decryptor {
+            buffer: vec![],
+            cipher: None,
+            cipher_key: sub_keys.cipher,
+            mac: hmac::Hmac::new_varkey(sub_keys.mac.as_bytes()).unwrap(),
+        }
+    }
+
+    /// Decrypts the next piece of the message, returning as much decrypted data as is available so far.
+    ///
+    /// The last block and the MAC are held back until `finish` is called.
+    pub fn update(&mut self, message: &[u8]) -> Vec<u8> {
+        use hmac::Mac;
+        self.buffer.extend_from_slice(message);
+        if self.cipher.is_none() {
+            if self.buffer.len() < 17 {
+                return vec![];
+            }
+            self.mac.input(&self.buffer[1..17]);
+            self.cipher = Some(Cbc::new(&self.cipher_key, &self.buffer[1..17]));
+            self.buffer.drain(..17);
+        }
+        let length = self.buffer.len().saturating_sub(MAC_SIZE + 16) / 16 * 16;
+        if length == 0 {
+            return vec![];
+        }
+        self.mac.input(&self.buffer[..length]);
+        if let Some(cipher) = &mut self.cipher {
+            cipher.decrypt_blocks(&mut self.buffer[..length]);
+        }
+        self.buffer.drain(..length).collect()
+    }
+
+    /// Verifies the MAC at the end of the message and returns the rest of the decrypted data.
+    ///
+    /// Fails if the message was truncated or the MAC doesn't match.
+    pub fn finish(mut self) -> Result<Vec<u8>, Error> {
+        use {block_modes::block_padding::Padding, hmac::Mac};
+        let cipher = match self.cipher {
+            Some(ref mut cipher) if self.buffer.len() == 16 + MAC_SIZE => cipher,
+            _ => return Err(Error::Decryption("message length")),
+        };
+        self.mac.input(&self.buffer[..16]);
+        if self.mac.verify(&self.buffer[16..]).is_err() {
+            return Err(Error::Decryption("message authentication code"));
+        }
+        cipher.decrypt_blocks(&mut self.buffer[..16]);
+        block_modes::block_padding::Pkcs7::unpad(&self.buffer[..16])
+            .map(|output| output.to_vec())
+            .map_err(|_| Error::Decryption("message padding"))
+    }
+}
 
 pub struct SubKeys {
-    cipher: [u8; 16],
-    mac: [u8; 16],
+    cipher: Key,
+    mac: Key,
 }
 
 impl SubKeys {
-    pub fn new(key: [u8; 16]) -> Self {
-        let mut hasher = sha2::Sha256::new();
-        hasher.input(key);
-        let hash = hasher.result();
-        let mut cipher = [0; 16];
-        let mut mac = [0; 16];
-        cipher.copy_from_slice(&hash[..16]);
-        mac.copy_from_slice(&hash[16..]);
-        SubKeys { cipher, mac }
+    /// Derives the cipher key and the MAC key from a key, by hashing a 128-bit key with SHA-256 and a 256-bit key with SHA-512.
+    pub fn new(key: Key) -> Self {
+        match key {
+            Key::Aes128(key) => {
+                let hash = sha2::Sha256::digest(&key);
+                let mut cipher = [0; 16];
+                let mut mac = [0; 16];
+                cipher.copy_from_slice(&hash[..16]);
+                mac.copy_from_slice(&hash[16..]);
+                SubKeys {
+                    cipher: Key::Aes128(cipher),
+                    mac: Key::Aes128(mac),
+                }
+            }
+            Key::Aes256(key) => {
+                let hash = sha2::Sha512::digest(&key);
+                let mut cipher = [0; 32];
+                let mut mac = [0; 32];
+                cipher.copy_from_slice(&hash[..32]);
+                mac.copy_from_slice(&hash[32..]);
+                SubKeys {
+                    cipher: Key::Aes256(cipher),
+                    mac: Key::Aes256(mac),
+                }
+            }
+        }
     }
 }
 
-pub fn create_key() -> [u8; 16] {
-    use rand_os::rand_core::RngCore;
-    let mut rng = rand_os::OsRng::new().unwrap();
+// AES in CBC mode with either key size, encrypting and decrypting whole blocks without padding.
+enum Cbc {
+    Aes128(block_modes::Cbc<aes::Aes128, block_modes::block_padding::Pkcs7>),
+    Aes256(block_modes::Cbc<aes::Aes256, block_modes::block_padding::Pkcs7>),
+}
+
+impl Cbc {
+    fn new(key: &Key, iv: &[u8]) -> Self {
+        use block_modes::BlockMode;
+        match key {
+            Key::Aes128(key) => Cbc::Aes128(block_modes::Cbc::new_fix(key[..].into(), iv.into())),
+            Key::Aes256(key) => Cbc::Aes256(block_modes::Cbc::new_fix(key[..].into(), iv.into())),
+        }
+    }
+
+    fn decrypt_blocks(&mut self, data: &mut [u8]) {
+        use block_modes::BlockMode;
+        match self {
+            Cbc::Aes128(cipher) => cipher.decrypt_blocks(to_blocks(data)),
+            Cbc::Aes256(cipher) => cipher.decrypt_blocks(to_blocks(data)),
+        }
+    }
+
+    fn encrypt_blocks(&mut self, data: &mut [u8]) {
+        use block_modes::BlockMode;
+        match self {
+            Cbc::Aes128(cipher) => cipher.encrypt_blocks(to_blocks(data)),
+            Cbc::Aes256(cipher) => cipher.encrypt_blocks(to_blocks(data)),
+        }
+    }
+}
+
+/// Creates a random 128-bit key.
+pub fn create_key() -> Result<Key, Error> {
     let mut output = [0; 16];
-    rng.fill_bytes(&mut output);
-    output
+    random_bytes(&mut output)?;
+    Ok(Key::Aes128(output))
 }
 
 pub fn create_user_passphrase_key(passphrase: &str, salt: &[u8]) -> [u8; 16] {
@@ -49,68 +200,192 @@ pub fn create_user_passphrase_key(passphrase: &str, salt: &[u8]) -> [u8; 16] {
     user_passphrase_key
 }
 
-pub fn decrypt_key(key: &[u8], message: &[u8]) -> Option<[u8; 16]> {
-    if key.len() == 16 && message.len() == 16 {
-        use aes::block_cipher_trait::BlockCipher;
-        let mut output = [0; 16];
-        output.copy_from_slice(message);
-        let cipher = aes::Aes128::new(key.into());
-        cipher.decrypt_block(output.as_mut().into());
-        for byte in &mut output {
-            *byte ^= 0x88;
+/// Derives the passphrase key of a user with Argon2id, as used by newer accounts.
+pub fn create_user_passphrase_key_argon2id(
+    passphrase: &str,
+    salt: &[u8],
+) -> Result<[u8; 32], Error> {
+    let config = argon2::Config {
+        ad: &[],
+        hash_length: 32,
+        lanes: 1,
+        mem_cost: 32768,
+        secret: &[],
+        thread_mode: argon2::ThreadMode::Sequential,
+        time_cost: 4,
+        variant: argon2::Variant::Argon2id,
+        version: argon2::Version::Version13,
+    };
+    let hash = argon2::hash_raw(passphrase.as_bytes(), salt, &config)
+        .map_err(|_| Error::KeyDerivation("argon2id"))?;
+    let mut user_passphrase_key = [0; 32];
+    user_passphrase_key.copy_from_slice(&hash);
+    Ok(user_passphrase_key)
+}
+
+/// Decrypts a key encrypted with `encrypt_key`.
+///
+/// Keys encrypted with a 256-bit key by older clients, without a MAC, are also accepted.
+pub fn decrypt_key(key: &Key, message: &[u8]) -> Option<Key> {
+    let output = match key {
+        Key::Aes256(_) if message.len() % 16 == 1 => {
+            decrypt_blocks_with_mac(&SubKeys::new(*key), message)?
+        }
+        _ => {
+            if message.len() != 16 && message.len() != 32 {
+                return None;
+            }
+            let mut output = message.to_vec();
+            Cbc::new(key, &FIXED_IV).decrypt_blocks(&mut output);
+            output
         }
-        Some(output)
-    } else {
-        None
+    };
+    Key::from_bytes(&output)
+}
+
+// Decrypts a value that is text, naming the value in the error. An empty value is not encrypted.
+pub(crate) fn decrypt_string(
+    session_key: Key,
+    value: &[u8],
+    name: &'static str,
+) -> Result<String, Error> {
+    if value.is_empty() {
+        return Ok(String::new());
     }
+    decrypt_with_mac(&SubKeys::new(session_key), value)
+        .and_then(|value| String::from_utf8(value).ok())
+        .ok_or(Error::Decryption(name))
 }
 
 pub fn decrypt_with_mac(sub_keys: &SubKeys, message: &[u8]) -> Option<Vec<u8>> {
-    use {block_modes::BlockMode, hmac::Mac};
-    if message.len() < MAC_SIZE || message.len() % 16 != 1 {
+    use block_modes::block_padding::Padding;
+    let output = decrypt_blocks_with_mac(sub_keys, message)?;
+    block_modes::block_padding::Pkcs7::unpad(&output)
+        .ok()
+        .map(|output| output.to_vec())
+}
+
+/// Derives the passphrase key of a user with the given key derivation function, giving a 128-bit key for bcrypt and a 256-bit key for Argon2id.
+pub fn derive_user_passphrase_key(
+    kdf_type: &super::salt::KdfType,
+    passphrase: &str,
+    salt: &[u8],
+) -> Result<Key, Error> {
+    use super::salt::KdfType;
+    match kdf_type {
+        KdfType::Argon2id => Ok(Key::Aes256(create_user_passphrase_key_argon2id(
+            passphrase, salt,
+        )?)),
+        KdfType::Bcrypt => Ok(Key::Aes128(create_user_passphrase_key(passphrase, salt))),
+        KdfType::Unknown(_) => Err(Error::KeyDerivation("key derivation function")),
+    }
+}
+
+/// Encrypts a key with another key.
+///
+/// With a 128-bit key, the key is encrypted with CBC using a fixed IV, without padding or a MAC. With a 256-bit key, the key is encrypted like with `encrypt_with_mac`, but without padding.
+pub fn encrypt_key(key: Key, message: Key) -> Result<Vec<u8>, Error> {
+    match key {
+        Key::Aes128(_) => {
+            let mut output = message.as_bytes().to_vec();
+            Cbc::new(&key, &FIXED_IV).encrypt_blocks(&mut output);
+            Ok(output)
+        }
+        Key::Aes256(_) => encrypt_blocks_with_mac(&SubKeys::new(key), message.as_bytes()),
+    }
+}
+
+pub fn encrypt_with_mac(sub_keys: &SubKeys, message: &[u8]) -> Result<Vec<u8>, Error> {
+    let padded_length = (message.len() + 16) / 16 * 16;
+    let mut padded = Vec::with_capacity(padded_length);
+    padded.extend_from_slice(message);
+    padded.resize(padded_length, (padded_length - message.len()) as _);
+    encrypt_blocks_with_mac(sub_keys, &padded)
+}
+
+/// Encrypts a message with RSA using OAEP padding with SHA-256.
+///
+/// The public key is in the format used by the server, which is the number of hexadecimal digits of the modulus as two bytes, followed by the modulus. The public exponent is always 65537.
+pub fn rsa_encrypt(public_key: &[u8], message: &[u8]) -> Result<Vec<u8>, Error> {
+    if public_key.len() < 2 {
+        return Err(Error::PublicKey);
+    }
+    let modulus_hex_length = usize::from(public_key[0]) << 8 | usize::from(public_key[1]);
+    if modulus_hex_length % 2 != 0 || public_key.len() != 2 + modulus_hex_length / 2 {
+        return Err(Error::PublicKey);
+    }
+    let modulus = num_bigint::BigUint::from_bytes_be(&public_key[2..]);
+    let key_size = (modulus.bits() + 7) / 8;
+    if message.len() + 2 * HASH_SIZE + 2 > key_size {
+        return Err(Error::PublicKey);
+    }
+    let mut block = vec![0; key_size];
+    random_bytes(&mut block[1..1 + HASH_SIZE])?;
+    block[1 + HASH_SIZE..1 + 2 * HASH_SIZE].copy_from_slice(&sha2::Sha256::digest(&[]));
+    block[key_size - message.len() - 1] = 1;
+    block[key_size - message.len()..].copy_from_slice(message);
+    {
+        let (seed, data_block) = block[1..].split_at_mut(HASH_SIZE);
+        mask_with_mgf1(seed, data_block);
+        mask_with_mgf1(data_block, seed);
+    }
+    let encrypted = num_bigint::BigUint::from_bytes_be(&block)
+        .modpow(&RSA_PUBLIC_EXPONENT.into(), &modulus)
+        .to_bytes_be();
+    let mut output = vec![0; key_size - encrypted.len()];
+    output.extend_from_slice(&encrypted);
+    Ok(output)
+}
+
+pub(crate) fn random_bytes(output: &mut [u8]) -> Result<(), Error> {
+    use rand_os::rand_core::RngCore;
+    let mut rng = rand_os::OsRng::new().map_err(Error::Rng)?;
+    rng.try_fill_bytes(output).map_err(Error::Rng)
+}
+
+// Verifies the MAC of a message in the format of `encrypt_with_mac` and decrypts it, leaving any padding in place.
+fn decrypt_blocks_with_mac(sub_keys: &SubKeys, message: &[u8]) -> Option<Vec<u8>> {
+    use hmac::Mac;
+    if message.len() < 1 + 16 + MAC_SIZE || message.len() % 16 != 1 {
         return None;
     }
-    let mut mac = hmac::Hmac::<sha2::Sha256>::new_varkey(&sub_keys.mac).unwrap();
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_varkey(sub_keys.mac.as_bytes()).unwrap();
     let message_without_mac = &message[1..message.len() - MAC_SIZE];
     mac.input(message_without_mac);
     if mac.verify(&message[message.len() - MAC_SIZE..]).is_err() {
         return None;
     }
-    let cipher = block_modes::Cbc::<aes::Aes128, block_modes::block_padding::Pkcs7>::new_fix(
-        sub_keys.cipher[..].into(),
-        message_without_mac[..16].into(),
-    );
-    cipher.decrypt_vec(&message_without_mac[16..]).ok()
-}
-
-pub fn encrypt_key(key: [u8; 16], mut message: [u8; 16]) -> [u8; 16] {
-    use aes::block_cipher_trait::BlockCipher;
-    for byte in &mut message {
-        *byte ^= 0x88;
-    }
-    let cipher = aes::Aes128::new(key[..].into());
-    cipher.encrypt_block(message.as_mut().into());
-    message
+    let mut output = message_without_mac[16..].to_vec();
+    Cbc::new(&sub_keys.cipher, &message_without_mac[..16]).decrypt_blocks(&mut output);
+    Some(output)
 }
 
-pub fn encrypt_with_mac(sub_keys: &SubKeys, message: &[u8]) -> Vec<u8> {
-    use {block_modes::BlockMode, hmac::Mac};
-    let length_before_mac = (message.len() + 16) / 16 * 16 + 17;
-    let mut output = Vec::with_capacity(length_before_mac + MAC_SIZE);
+// Encrypts a message whose length is a multiple of the block size with a random IV, prefixed with a version byte and followed by a MAC.
+fn encrypt_blocks_with_mac(sub_keys: &SubKeys, message: &[u8]) -> Result<Vec<u8>, Error> {
+    use hmac::Mac;
+    let mut output = Vec::with_capacity(17 + message.len() + MAC_SIZE);
     output.push(1);
-    let iv = create_key();
+    let mut iv = [0; 16];
+    random_bytes(&mut iv)?;
     output.extend_from_slice(&iv);
     output.extend_from_slice(message);
-    output.resize(length_before_mac, (length_before_mac - output.len()) as _);
-    let mut cipher = block_modes::Cbc::<aes::Aes128, block_modes::block_padding::Pkcs7>::new_fix(
-        sub_keys.cipher[..].into(),
-        iv[..].into(),
-    );
-    cipher.encrypt_blocks(to_blocks(&mut output[17..]));
-    let mut mac = hmac::Hmac::<sha2::Sha256>::new_varkey(&sub_keys.mac).unwrap();
+    Cbc::new(&sub_keys.cipher, &iv).encrypt_blocks(&mut output[17..]);
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_varkey(sub_keys.mac.as_bytes()).unwrap();
     mac.input(&output[1..]);
     output.extend_from_slice(&mac.result().code());
-    output
+    Ok(output)
+}
+
+// Applies the mask generation function MGF1 with SHA-256 as defined in RFC 8017.
+fn mask_with_mgf1(seed: &[u8], output: &mut [u8]) {
+    for (counter, chunk) in output.chunks_mut(HASH_SIZE).enumerate() {
+        let mut hasher = sha2::Sha256::new();
+        hasher.input(seed);
+        hasher.input(&(counter as u32).to_be_bytes());
+        for (byte, mask) in chunk.iter_mut().zip(hasher.result().iter()) {
+            *byte ^= mask;
+        }
+    }
 }
 
 // This function comes from the block-modes crate, but is unfortunately private.
@@ -125,3 +400,283 @@ where
         std::slice::from_raw_parts_mut(data.as_ptr() as *mut GenericArray<u8, N>, data.len() / n)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Key;
+
+    fn random_key_128() -> Key {
+        super::create_key().unwrap()
+    }
+
+    fn random_key_256() -> Key {
+        let mut key = [0; 32];
+        super::random_bytes(&mut key).unwrap();
+        Key::Aes256(key)
+    }
+
+    fn sequence(length: usize) -> Vec<u8> {
+        (0..length).map(|index| index as u8).collect()
+    }
+
+    #[test]
+    fn key_from_bytes() {
+        assert_eq!(Key::from_bytes(&[1; 16]), Some(Key::Aes128([1; 16])));
+        assert_eq!(Key::from_bytes(&[2; 32]), Some(Key::Aes256([2; 32])));
+        assert_eq!(Key::from_bytes(&[3; 24]), None);
+        assert_eq!(Key::Aes256([4; 32]).as_bytes(), &[4; 32][..]);
+    }
+
+    #[test]
+    fn key_wrapping_round_trip() {
+        for &(key, message) in &[
+            (random_key_128(), random_key_128()),
+            (random_key_128(), random_key_256()),
+            (random_key_256(), random_key_128()),
+            (random_key_256(), random_key_256()),
+        ] {
+            let encrypted = super::encrypt_key(key, message).unwrap();
+            assert_eq!(super::decrypt_key(&key, &encrypted), Some(message));
+        }
+    }
+
+    #[test]
+    fn key_wrapping_with_256_bit_key_is_authenticated() {
+        let key = random_key_256();
+        let mut encrypted = super::encrypt_key(key, random_key_128()).unwrap();
+        assert_eq!(encrypted.len(), 1 + 16 + 16 + super::MAC_SIZE);
+        encrypted[20] ^= 1;
+        assert_eq!(super::decrypt_key(&key, &encrypted), None);
+    }
+
+    // The AES-128 example of FIPS 197 appendix C.1. A key was encrypted by XORing it with 0x88 and encrypting the result with AES-128 in ECB mode, which is what CBC with the fixed IV does for a single block.
+    #[test]
+    fn key_wrapping_with_128_bit_key_known_answer() {
+        let key = Key::from_bytes(&sequence(16)).unwrap();
+        let mut message = [0; 16];
+        for (index, byte) in message.iter_mut().enumerate() {
+            *byte = (index as u8 * 0x11) ^ 0x88;
+        }
+        let encrypted = [
+            0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4,
+            0xc5, 0x5a,
+        ];
+        assert_eq!(
+            super::encrypt_key(key, Key::Aes128(message)).unwrap(),
+            &encrypted[..]
+        );
+        assert_eq!(
+            super::decrypt_key(&key, &encrypted),
+            Some(Key::Aes128(message))
+        );
+    }
+
+    // The AES-256 example of FIPS 197 appendix C.3, as a key encrypted by an older client with a 256-bit key and the fixed IV, without a MAC.
+    #[test]
+    fn key_unwrapping_with_256_bit_key_without_mac_known_answer() {
+        let key = Key::from_bytes(&sequence(32)).unwrap();
+        let mut message = [0; 16];
+        for (index, byte) in message.iter_mut().enumerate() {
+            *byte = (index as u8 * 0x11) ^ 0x88;
+        }
+        let encrypted = [
+            0x8e, 0xa2, 0xb7, 0xca, 0x51, 0x67, 0x45, 0xbf, 0xea, 0xfc, 0x49, 0x90, 0x4b, 0x49,
+            0x60, 0x89,
+        ];
+        assert_eq!(
+            super::decrypt_key(&key, &encrypted),
+            Some(Key::Aes128(message))
+        );
+    }
+
+    #[test]
+    fn encryption_with_mac_round_trip() {
+        for &key in &[random_key_128(), random_key_256()] {
+            let sub_keys = super::SubKeys::new(key);
+            for &length in &[0, 1, 15, 16, 17, 100] {
+                let message = sequence(length);
+                let encrypted = super::encrypt_with_mac(&sub_keys, &message).unwrap();
+                assert_eq!(encrypted.len() % 16, 1);
+                assert_eq!(
+                    super::decrypt_with_mac(&sub_keys, &encrypted),
+                    Some(message)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decryption_with_mac_rejects_invalid_messages() {
+        let sub_keys = super::SubKeys::new(random_key_256());
+        let encrypted = super::encrypt_with_mac(&sub_keys, b"message").unwrap();
+        let mut tampered = encrypted.clone();
+        tampered[1] ^= 1;
+        assert_eq!(super::decrypt_with_mac(&sub_keys, &tampered), None);
+        assert_eq!(
+            super::decrypt_with_mac(&sub_keys, &encrypted[..encrypted.len() - 16]),
+            None
+        );
+        assert_eq!(super::decrypt_with_mac(&sub_keys, &encrypted[..33]), None);
+        let other_sub_keys = super::SubKeys::new(random_key_256());
+        assert_eq!(super::decrypt_with_mac(&other_sub_keys, &encrypted), None);
+    }
+
+    // Feeds the message to a decryptor in pieces of the given sizes, repeating the sizes until the whole message has been fed.
+    fn decrypt_in_pieces(
+        sub_keys: &super::SubKeys,
+        message: &[u8],
+        sizes: &[usize],
+    ) -> Result<Vec<u8>, super::Error> {
+        let mut decryptor = super::Decryptor::new(sub_keys);
+        let mut output = vec![];
+        let mut remaining = message;
+        for &size in sizes.iter().cycle() {
+            if remaining.is_empty() {
+                break;
+            }
+            let (piece, rest) = remaining.split_at(size.min(remaining.len()));
+            output.extend(decryptor.update(piece));
+            remaining = rest;
+        }
+        output.extend(decryptor.finish()?);
+        Ok(output)
+    }
+
+    #[test]
+    fn decryptor_matches_decryption_with_mac() {
+        for &key in &[random_key_128(), random_key_256()] {
+            let sub_keys = super::SubKeys::new(key);
+            for &length in &[0, 1, 16, 100, 1000] {
+                let message = sequence(length);
+                let encrypted = super::encrypt_with_mac(&sub_keys, &message).unwrap();
+                for sizes in &[&[1][..], &[17], &[5, 33, 2], &[encrypted.len()]] {
+                    assert_eq!(
+                        decrypt_in_pieces(&sub_keys, &encrypted, sizes).unwrap(),
+                        message
+                    );
+                }
+                assert_eq!(
+                    super::decrypt_with_mac(&sub_keys, &encrypted),
+                    Some(message)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decryptor_rejects_invalid_messages() {
+        let sub_keys = super::SubKeys::new(random_key_128());
+        let encrypted = super::encrypt_with_mac(&sub_keys, &sequence(100)).unwrap();
+        for truncated in &[
+            &encrypted[..encrypted.len() - 1],
+            &encrypted[..encrypted.len() - 16],
+            &encrypted[..10],
+        ] {
+            assert!(decrypt_in_pieces(&sub_keys, truncated, &[7]).is_err());
+        }
+        for &index in &[1, 20, 70, encrypted.len() - 1] {
+            let mut tampered = encrypted.clone();
+            tampered[index] ^= 1;
+            assert!(decrypt_in_pieces(&sub_keys, &tampered, &[7]).is_err());
+        }
+    }
+
+    // A 1024-bit RSA key made for these tests only, with the public exponent used by the server.
+    const RSA_MODULUS: &[u8] = b"b6c81a986198be3512fd282a15d2b796755db065e1ea887b854ee7c46acb96f189dffc8ba33c230ee6bfd65a61117753b1237b737fa77f41137f4903e0ff3bac8c01fac202f542aae4f266c54bf1b86316c357f93ce75706b4dfdf351fd6eb51032c71526007ee1a9dcde458f5474f69a5533bca7e31eb74daae3d4f0613c2db";
+    const RSA_PRIVATE_EXPONENT: &[u8] = b"b67c6c090d0fa97f65b8f89ba3f4203508728486039adbaeaef68b70cfcaf9a5a728c8844571e9f29bb095fae004c724f8aadb3b020d2180c04d898164e68e14d199eb776cc6f2abbfbbf2d97cf542ac7fa0dc0c41b71cc3f6998e3a4eecb8ddd1197ad4f8aa0a4257abfac4cf90d2a0975e9306278c68c2371a3f0fa5ca9309";
+
+    // The public key in the format given by the server, prefixed with the number of hexadecimal digits of the modulus.
+    fn rsa_public_key() -> Vec<u8> {
+        let mut public_key = vec![1, 0];
+        public_key.extend(
+            num_bigint::BigUint::parse_bytes(RSA_MODULUS, 16)
+                .unwrap()
+                .to_bytes_be(),
+        );
+        public_key
+    }
+
+    // Decrypts with the private key and removes the OAEP padding.
+    fn rsa_decrypt(encrypted: &[u8]) -> Vec<u8> {
+        use sha2::Digest;
+        let modulus = num_bigint::BigUint::parse_bytes(RSA_MODULUS, 16).unwrap();
+        let exponent = num_bigint::BigUint::parse_bytes(RSA_PRIVATE_EXPONENT, 16).unwrap();
+        let decrypted = num_bigint::BigUint::from_bytes_be(encrypted)
+            .modpow(&exponent, &modulus)
+            .to_bytes_be();
+        let mut block = vec![0; 128 - decrypted.len()];
+        block.extend_from_slice(&decrypted);
+        assert_eq!(block[0], 0);
+        let (seed, data_block) = block[1..].split_at_mut(super::HASH_SIZE);
+        super::mask_with_mgf1(data_block, seed);
+        super::mask_with_mgf1(seed, data_block);
+        let (label_hash, padded_message) = data_block.split_at(super::HASH_SIZE);
+        assert_eq!(label_hash, &sha2::Sha256::digest(&[])[..]);
+        let start = padded_message.iter().position(|&byte| byte != 0).unwrap();
+        assert_eq!(padded_message[start], 1);
+        padded_message[start + 1..].to_vec()
+    }
+
+    #[test]
+    fn rsa_encryption_round_trip() {
+        let public_key = rsa_public_key();
+        for &length in &[0, 16, 32, 62] {
+            let message = sequence(length);
+            let encrypted = super::rsa_encrypt(&public_key, &message).unwrap();
+            assert_eq!(encrypted.len(), 128);
+            assert_eq!(rsa_decrypt(&encrypted), message);
+            assert_ne!(
+                super::rsa_encrypt(&public_key, &message).unwrap(),
+                encrypted
+            );
+        }
+    }
+
+    #[test]
+    fn rsa_encryption_rejects_invalid_input() {
+        let public_key = rsa_public_key();
+        let mut odd_length = vec![0, 255];
+        odd_length.extend_from_slice(&public_key[2..130]);
+        let mut wrong_length = public_key.clone();
+        wrong_length[1] = 2;
+        for invalid_key in &[
+            &[][..],
+            &[1],
+            &odd_length,
+            &wrong_length,
+            &public_key[..129],
+        ] {
+            match super::rsa_encrypt(invalid_key, &sequence(16)) {
+                Err(super::Error::PublicKey) => {}
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+        match super::rsa_encrypt(&public_key, &sequence(63)) {
+            Err(super::Error::PublicKey) => {}
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    // Computed independently with the Argon2id implementation of OpenSSL 3.
+    #[test]
+    fn argon2id_known_answer() {
+        let key = [
+            0x2c, 0xb6, 0xa1, 0x07, 0xa9, 0xd3, 0xbf, 0x7e, 0x09, 0x37, 0x9e, 0x81, 0x67, 0x6c,
+            0x04, 0x6e, 0x05, 0xaf, 0xa6, 0x2f, 0x66, 0xb3, 0x22, 0x05, 0x5a, 0xdc, 0xa8, 0x15,
+            0xc2, 0x3a, 0x7e, 0x9d,
+        ];
+        assert_eq!(
+            super::create_user_passphrase_key_argon2id("password", &sequence(16)).unwrap(),
+            key
+        );
+        assert_eq!(
+            super::derive_user_passphrase_key(
+                &super::super::salt::KdfType::Argon2id,
+                "password",
+                &sequence(16)
+            )
+            .unwrap(),
+            Key::Aes256(key)
+        );
+    }
+}
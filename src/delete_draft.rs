@@ -0,0 +1,19 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{
+    id::{FolderId, MailId},
+    Error,
+};
+
+/// Permanently deletes a draft from the given folder, which is normally the drafts folder.
+pub fn delete_draft<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    draft: &MailId,
+    folder: &FolderId,
+) -> impl futures::Future<Error = Error, Item = ()> {
+    super::delete_mails::delete_mails(client, config, access_token, &[draft], folder)
+}
@@ -2,47 +2,73 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
-use futures::Future;
+use super::{
+    id::FolderId,
+    mailfolder::{Folder, MailFolderType},
+    Error,
+};
+use futures::{
+    future::{self, Either},
+    Future,
+};
 use serde_derive::Serialize;
 
 #[derive(Serialize)]
 struct Request<'a> {
-    folders: &'a [&'a (String, String)],
+    folders: &'a [&'a FolderId],
     #[serde(rename = "_format")]
     format: &'a str,
 }
 
 pub fn delete_mail_folder<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    folder: &(String, String),
+    folder: &Folder,
 ) -> impl futures::Future<Error = Error, Item = ()> {
-    let request_body = serde_json::to_string(&Request {
-        folders: &[folder],
+    delete_mail_folders(client, config, access_token, &[folder])
+}
+
+/// Deletes the folders in a single request, or succeeds without making any request if there are no folders.
+///
+/// Fails with `Error::SystemFolder` without making any request if any of the folders is a system folder.
+pub fn delete_mail_folders<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    folders: &[&Folder],
+) -> impl futures::Future<Error = Error, Item = ()> {
+    if folders.is_empty() {
+        return Either::A(future::ok(()));
+    }
+    if let Some(folder) = folders
+        .iter()
+        .find(|folder| folder.folder_type != MailFolderType::Custom)
+    {
+        return Either::A(future::err(Error::SystemFolder(folder.id.clone())));
+    }
+    let url = format!("{}/rest/tutanota/mailfolderservice", config.origin);
+    let folders: Vec<_> = folders.iter().map(|folder| &folder.id).collect();
+    let request = serde_json::to_string(&Request {
+        folders: &folders,
         format: "0",
     })
-    .unwrap();
-    let mut request = hyper::Request::new(hyper::Body::from(request_body));
-    *request.method_mut() = hyper::Method::DELETE;
-    // XXX Don't unwrap, but gracefully return error.
-    request.headers_mut().insert(
-        "accessToken",
-        hyper::header::HeaderValue::from_str(access_token).unwrap(),
-    );
-    request
-        .headers_mut()
-        .insert("v", hyper::header::HeaderValue::from_str("30").unwrap());
-    *request.uri_mut() =
-        hyper::Uri::from_static("https://mail.tutanota.com/rest/tutanota/mailfolderservice");
-    client.request(request).then(|result| match result {
-        Err(error) => Err(Error::Network(error)),
-        Ok(response) => {
-            if response.status() == hyper::StatusCode::OK {
-                Ok(())
-            } else {
-                Err(Error::Status(response))
-            }
-        }
-    })
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        let mut request = super::protocol::request(
+            hyper::Method::DELETE,
+            &url,
+            Some(access_token),
+            request_body.into(),
+        )?;
+        request
+            .headers_mut()
+            .insert("v", hyper::header::HeaderValue::from_static("30"));
+        Ok(request)
+    });
+    Either::B(
+        super::protocol::send(client, request)
+            .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+            .map(|_| ()),
+    )
 }
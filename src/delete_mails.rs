@@ -0,0 +1,46 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{
+    id::{FolderId, MailId},
+    Error,
+};
+use futures::Future;
+use serde_derive::Serialize;
+
+#[derive(Serialize)]
+struct Request<'a> {
+    #[serde(with = "super::protocol::format")]
+    _format: (),
+    folder: &'a FolderId,
+    mails: &'a [&'a MailId],
+}
+
+/// Permanently deletes the mails from the given folder, which all of them must be in.
+pub fn delete_mails<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    mails: &[&MailId],
+    folder: &FolderId,
+) -> impl futures::Future<Error = Error, Item = ()> {
+    let url = format!("{}/rest/tutanota/mailservice", config.origin);
+    let request = serde_json::to_string(&Request {
+        _format: (),
+        folder,
+        mails,
+    })
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        super::protocol::request(
+            hyper::Method::DELETE,
+            &url,
+            Some(access_token),
+            request_body.into(),
+        )
+    });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+        .map(|_| ())
+}
@@ -0,0 +1,33 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::Error;
+use futures::{
+    future::{self, Either},
+    Future,
+};
+
+/// Closes the session the access token belongs to, so the access token can no longer be used.
+pub fn delete_session<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+) -> impl futures::Future<Error = Error, Item = ()> {
+    let session = match super::session::session_id(access_token) {
+        Err(error) => return Either::A(future::err(error)),
+        Ok(session) => session,
+    };
+    let url = format!("{}/rest/sys/session/{}", config.origin, session);
+    let request = super::protocol::request(
+        hyper::Method::DELETE,
+        &url,
+        Some(access_token),
+        Default::default(),
+    );
+    Either::B(
+        super::protocol::send(client, request)
+            .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+            .map(|_| ()),
+    )
+}
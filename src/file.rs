@@ -2,15 +2,20 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{
+    id::{FileDataId, FileId},
+    Error, Key,
+};
 use futures::Future;
 use serde_derive::Deserialize;
 
 #[derive(Debug, Deserialize)]
 pub struct File {
-    pub data: String,
+    pub data: FileDataId,
     #[serde(with = "super::protocol::format")]
     _format: (),
+    #[serde(rename = "_id")]
+    pub id: FileId,
     #[serde(with = "super::protocol::base64", rename = "mimeType")]
     pub mime_type: Vec<u8>,
     #[serde(with = "super::protocol::base64")]
@@ -20,15 +25,21 @@ pub struct File {
     pub size: String,
 }
 
+impl File {
+    /// Decrypts the session key of the file, which the name, MIME type and data of the file are encrypted with.
+    pub fn session_key(&self, mail_group_key: Key) -> Result<Key, Error> {
+        super::decrypt_key(&mail_group_key, &self.owner_enc_session_key)
+            .ok_or(Error::Decryption("file session key"))
+    }
+}
+
 pub fn fetch_file<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    file: &(String, String),
+    file: &FileId,
 ) -> impl futures::Future<Error = Error, Item = File> {
-    let url = format!(
-        "https://mail.tutanota.com/rest/tutanota/file/{}/{}",
-        file.0, file.1
-    );
+    let url = format!("{}/rest/tutanota/file/{}", config.origin, file);
     super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
         serde_json::from_slice::<File>(&response_body).map_err(Error::Format)
     })
@@ -2,33 +2,102 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
-use futures::Future;
+use super::{
+    id::{FileDataId, FileId, GroupId},
+    Error, Key,
+};
+use futures::{
+    future::{self, Either},
+    Async, Future, Poll, Stream,
+};
+use serde_derive::{Deserialize, Serialize};
+
+/// A stream of decrypted file data, created by `decrypt_filedata`.
+pub struct DecryptedFileData {
+    body: hyper::Body,
+    decryptor: Option<super::Decryptor>,
+}
+
+impl Stream for DecryptedFileData {
+    type Error = Error;
+    type Item = Vec<u8>;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, Error> {
+        loop {
+            let decryptor = match &mut self.decryptor {
+                None => return Ok(Async::Ready(None)),
+                Some(decryptor) => decryptor,
+            };
+            match self.body.poll().map_err(Error::Network)? {
+                Async::NotReady => return Ok(Async::NotReady),
+                Async::Ready(Some(chunk)) => {
+                    let output = decryptor.update(&chunk);
+                    if !output.is_empty() {
+                        return Ok(Async::Ready(Some(output)));
+                    }
+                }
+                Async::Ready(None) => {
+                    let output = self.decryptor.take().unwrap().finish()?;
+                    if !output.is_empty() {
+                        return Ok(Async::Ready(Some(output)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct UploadRequest<'a> {
+    #[serde(with = "super::protocol::format")]
+    _format: (),
+    group: &'a GroupId,
+    size: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadResponse {
+    #[serde(rename = "_format", with = "super::protocol::format")]
+    _format: (),
+    file_data: FileDataId,
+}
+
+/// Decrypts file data as it's received, yielding the decrypted data one piece at a time.
+///
+/// The stream fails at the end if the MAC doesn't match, so the data must not be trusted until the stream has ended successfully.
+pub fn decrypt_filedata(body: hyper::Body, session_key: Key) -> DecryptedFileData {
+    DecryptedFileData {
+        body,
+        decryptor: Some(super::Decryptor::new(&super::SubKeys::new(session_key))),
+    }
+}
 
 pub fn fetch_filedata<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    file: &(String, String),
+    file: &FileId,
 ) -> impl hyper::rt::Future<Error = Error, Item = hyper::Body> {
-    let mut request = hyper::Request::new(Default::default());
-    // XXX Don't unwrap, but gracefully return error.
-    request.headers_mut().insert(
-        "accessToken",
-        hyper::header::HeaderValue::from_str(access_token).unwrap(),
-    );
-    let url = format!(
-        "https://mail.tutanota.com/rest/tutanota/filedataservice?_body=%7B%22_format%22%3A%220%22%2C%22base64%22%3A%220%22%2C%22file%22%3A%5B{}%2C{}%5D%7D",
-        urlencoding::encode(&serde_json::to_string(&file.0).unwrap()),
-        urlencoding::encode(&serde_json::to_string(&file.1).unwrap()),
-    );
-    // XXX Don't unwrap, but gracefully return error.
-    *request.uri_mut() = url.parse().unwrap();
-    client.request(request).then(|result| match result {
-        Err(error) => Err(Error::Network(error)),
-        Ok(response) => {
-            if response.status() != hyper::StatusCode::OK {
-                Err(Error::Status(response))
-            } else if match response.headers().get(hyper::header::CONTENT_TYPE) {
+    let request = serde_json::to_string(file)
+        .map_err(Error::Serialization)
+        .and_then(|file| {
+            let url = format!(
+                "{}/rest/tutanota/filedataservice?_body=%7B%22_format%22%3A%220%22%2C%22base64%22%3A%220%22%2C%22file%22%3A{}%7D",
+                config.origin,
+                urlencoding::encode(&file),
+            );
+            super::protocol::request(
+                hyper::Method::GET,
+                &url,
+                Some(access_token),
+                Default::default(),
+            )
+        });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
                 None => true,
                 Some(value) => value.as_bytes() != b"application/octet-stream",
             } {
@@ -36,6 +105,85 @@ pub fn fetch_filedata<C: 'static + hyper::client::connect::Connect>(
             } else {
                 Ok(response.into_body())
             }
-        }
+        })
+}
+
+/// Uploads the data of a file, already encrypted with the session key of the file, returning the identifier of the file data.
+///
+/// The file data is first created with the given size and owner group, and then the data is sent in a second request.
+pub fn upload_filedata<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    group: &GroupId,
+    data: Vec<u8>,
+) -> impl hyper::rt::Future<Error = Error, Item = FileDataId> {
+    let url = format!("{}/rest/tutanota/filedataservice", config.origin);
+    let request = serde_json::to_string(&UploadRequest {
+        _format: (),
+        group,
+        size: data.len().to_string(),
+    })
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        super::protocol::request(
+            hyper::Method::POST,
+            &url,
+            Some(access_token),
+            request_body.into(),
+        )
+    });
+    let client = client.clone();
+    let access_token = access_token.to_owned();
+    super::protocol::send(&client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
+                None => true,
+                Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
+            } {
+                Either::A(future::err(Error::ContentType(response)))
+            } else {
+                Either::B(response.into_body().concat2().then(|result| match result {
+                    Err(error) => Err(Error::Network(error)),
+                    Ok(response_body) => {
+                        match serde_json::from_slice::<UploadResponse>(&response_body) {
+                            Err(error) => Err(Error::Format(error)),
+                            Ok(response_data) => Ok(response_data.file_data),
+                        }
+                    }
+                }))
+            }
+        })
+        .and_then(move |file_data| {
+            let request = super::protocol::request(
+                hyper::Method::PUT,
+                &format!("{}?fileDataId={}", url, urlencoding::encode(&file_data.0)),
+                Some(&access_token),
+                data.into(),
+            )
+            .map(|mut request| {
+                request.headers_mut().insert(
+                    hyper::header::CONTENT_TYPE,
+                    hyper::header::HeaderValue::from_static("application/octet-stream"),
+                );
+                request
+            });
+            super::protocol::send(&client, request)
+                .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+                .map(|_| file_data)
+        })
+}
+
+/// Decrypts file data as it's received and writes it to the writer, returning the writer when all data has been written.
+///
+/// Decrypted data is written before the MAC has been verified, so the written data must be discarded if this fails.
+pub fn write_filedata<W: std::io::Write>(
+    body: hyper::Body,
+    session_key: Key,
+    writer: W,
+) -> impl hyper::rt::Future<Error = Error, Item = W> {
+    decrypt_filedata(body, session_key).fold(writer, |mut writer, data| {
+        writer.write_all(&data).map(|_| writer).map_err(Error::Io)
     })
 }
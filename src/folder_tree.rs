@@ -0,0 +1,189 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{id::FolderListId, mailfolder::Folder, range::Range, Error, Key};
+use futures::{
+    future::{self, Loop},
+    Future, Stream,
+};
+
+/// A folder with its name and its sub folders.
+#[derive(Debug)]
+pub struct FolderNode {
+    pub children: Vec<FolderNode>,
+    pub folder: Folder,
+    /// The name of the folder as given by `Folder::display_name`.
+    pub name: String,
+    /// The names of the folder and its ancestors separated by slashes, for example `Inbox/Projects/2026`.
+    pub path: String,
+}
+
+impl FolderNode {
+    /// Finds the folder with the given path among this folder and its descendants.
+    pub fn find(&self, path: &str) -> Option<&FolderNode> {
+        if self.path == path {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(path))
+    }
+}
+
+/// Fetches the folders in the list and all their descendants, one level at a time, and decrypts their names.
+///
+/// System folders are named after their type, so a folder created in the inbox has a path like `Inbox/Projects`.
+pub fn fetch_folder_tree<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    mail_group_key: Key,
+    folders: &FolderListId,
+) -> impl futures::Future<Error = Error, Item = Vec<FolderNode>> {
+    let client = client.clone();
+    let config = config.clone();
+    let access_token = access_token.to_owned();
+    future::loop_fn(
+        (vec![], vec![(folders.clone(), None)]),
+        move |(mut nodes, lists): (
+            Vec<(FolderNode, Option<usize>)>,
+            Vec<(FolderListId, Option<usize>)>,
+        )| {
+            let level = lists
+                .into_iter()
+                .map(|(list, parent)| {
+                    super::mailfolder::stream_mailfolder(
+                        &client,
+                        &config,
+                        &access_token,
+                        &list,
+                        Range::first(1000),
+                    )
+                    .collect()
+                    .map(move |folders| (folders, parent))
+                })
+                .collect::<Vec<_>>();
+            future::join_all(level).and_then(move |level| -> Result<_, Error> {
+                let mut lists = vec![];
+                for (folders, parent) in level {
+                    for folder in folders {
+                        let name = folder.display_name(mail_group_key)?;
+                        lists.push((folder.sub_folders.clone(), Some(nodes.len())));
+                        add_node(&mut nodes, folder, name, parent);
+                    }
+                }
+                Ok(if lists.is_empty() {
+                    Loop::Break(nodes)
+                } else {
+                    Loop::Continue((nodes, lists))
+                })
+            })
+        },
+    )
+    .map(build_tree)
+}
+
+// Adds a node for the folder after the node of its parent, with the path of the parent followed by the name of the folder.
+fn add_node(
+    nodes: &mut Vec<(FolderNode, Option<usize>)>,
+    folder: Folder,
+    name: String,
+    parent: Option<usize>,
+) {
+    let path = match parent {
+        None => name.clone(),
+        Some(parent) => format!("{}/{}", nodes[parent].0.path, name),
+    };
+    nodes.push((
+        FolderNode {
+            children: vec![],
+            folder,
+            name,
+            path,
+        },
+        parent,
+    ));
+}
+
+// Each node comes after its parent, so when walking the nodes backwards, all children of a node have been added to it when the node is reached.
+fn build_tree(nodes: Vec<(FolderNode, Option<usize>)>) -> Vec<FolderNode> {
+    let mut parents = Vec::with_capacity(nodes.len());
+    let mut slots = Vec::with_capacity(nodes.len());
+    for (node, parent) in nodes {
+        parents.push(parent);
+        slots.push(Some(node));
+    }
+    let mut roots = vec![];
+    for index in (0..slots.len()).rev() {
+        if let Some(mut node) = slots[index].take() {
+            node.children.reverse();
+            match parents[index] {
+                None => roots.push(node),
+                Some(parent) => {
+                    if let Some(parent) = &mut slots[parent] {
+                        parent.children.push(node);
+                    }
+                }
+            }
+        }
+    }
+    roots.reverse();
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FolderNode;
+
+    fn folder(id: &str) -> super::Folder {
+        serde_json::from_value(serde_json::json!({
+            "_format": "0",
+            "_id": ["folders", id],
+            "_ownerEncSessionKey": "",
+            "_ownerGroup": "group",
+            "_permissions": "permissions",
+            "folderType": "0",
+            "mails": "mails",
+            "name": "",
+            "parentFolder": null,
+            "subFolders": "subfolders",
+        }))
+        .unwrap()
+    }
+
+    fn paths(nodes: &[FolderNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.path.as_str()).collect()
+    }
+
+    #[test]
+    fn build_tree() {
+        // The nodes in the order they are fetched, one level at a time.
+        let mut nodes = vec![];
+        for &(name, parent) in &[
+            ("Inbox", None),
+            ("Sent", None),
+            ("Projects", Some(0)),
+            ("Receipts", Some(0)),
+            ("Replies", Some(1)),
+            ("2026", Some(2)),
+            ("2025", Some(2)),
+        ] {
+            super::add_node(&mut nodes, folder(name), name.to_owned(), parent);
+        }
+        let tree = super::build_tree(nodes);
+        assert_eq!(paths(&tree), ["Inbox", "Sent"]);
+        assert_eq!(
+            paths(&tree[0].children),
+            ["Inbox/Projects", "Inbox/Receipts"]
+        );
+        assert_eq!(
+            paths(&tree[0].children[0].children),
+            ["Inbox/Projects/2026", "Inbox/Projects/2025"]
+        );
+        assert!(tree[0].children[1].children.is_empty());
+        assert_eq!(paths(&tree[1].children), ["Sent/Replies"]);
+        let node = tree[0].find("Inbox/Projects/2025").unwrap();
+        assert_eq!(node.name, "2025");
+        assert_eq!(node.folder.id.to_string(), "folders/2025");
+        assert!(tree[1].find("Inbox/Projects").is_none());
+    }
+}
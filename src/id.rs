@@ -0,0 +1,113 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+//! Identifiers of entities.
+//!
+//! Each kind of identifier has its own type so that an identifier of one kind of entity can't be passed where another kind is expected.
+
+use serde_derive::{Deserialize, Serialize};
+
+macro_rules! id {
+    ($(#[$attribute:meta])* $name:ident) => {
+        $(#[$attribute])*
+        #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+        #[serde(transparent)]
+        pub struct $name(pub String);
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str(&self.0)
+            }
+        }
+    };
+}
+
+id! {
+    /// Identifier of an entity within a list, or of an entity not stored in a list.
+    ElementId
+}
+
+impl ElementId {
+    /// The identifier sorted before all other identifiers, for starting a range at the beginning of a list.
+    pub fn min() -> Self {
+        ElementId("------------".into())
+    }
+
+    /// The identifier sorted after all other identifiers, for starting a range at the end of a list.
+    pub fn max() -> Self {
+        ElementId("zzzzzzzzzzzz".into())
+    }
+}
+
+id! {
+    /// Identifier of the data of a file.
+    FileDataId
+}
+
+id! {
+    /// Identifier of a list of files.
+    FileListId
+}
+
+id! {
+    /// Identifier of a list of mail folders.
+    FolderListId
+}
+
+id! {
+    /// Identifier of a group.
+    GroupId
+}
+
+id! {
+    /// Identifier of a list of entities of any kind.
+    ListId
+}
+
+id! {
+    /// Identifier of the body of a mail.
+    MailBodyId
+}
+
+id! {
+    /// Identifier of a list of mails.
+    MailListId
+}
+
+id! {
+    /// Identifier of a mailbox.
+    MailboxId
+}
+
+id! {
+    /// Identifier of a user.
+    UserId
+}
+
+/// Identifier of an entity stored in a list, consisting of the identifier of the list and the identifier of the entity within the list.
+///
+/// It's serialized as an array of two strings. It's displayed as the two identifiers separated by a slash, which is how it's used in URLs.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct IdTuple<L = ListId, E = ElementId>(pub L, pub E);
+
+impl<L: std::fmt::Display, E: std::fmt::Display> std::fmt::Display for IdTuple<L, E> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "{}/{}", self.0, self.1)
+    }
+}
+
+/// Identifier of a conversation entry.
+pub type ConversationEntryId = IdTuple;
+
+/// Identifier of a file.
+pub type FileId = IdTuple<FileListId>;
+
+/// Identifier of a mail folder.
+pub type FolderId = IdTuple<FolderListId>;
+
+/// Identifier of a mail.
+pub type MailId = IdTuple<MailListId>;
+
+/// Identifier of a session.
+pub type SessionId = IdTuple;
@@ -2,25 +2,75 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
+// Defines an enum for a value the server represents as a string of digits, with a variant for each known value and a fallback for values not known to this crate.
+macro_rules! string_enum {
+    ($(#[$attribute:meta])* $name:ident { $($(#[$variant_attribute:meta])* $variant:ident = $value:literal,)* }) => {
+        $(#[$attribute])*
+        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        pub enum $name {
+            $($(#[$variant_attribute])* $variant,)*
+            /// A value not known to this crate.
+            Unknown(String),
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = <String as serde::Deserialize>::deserialize(deserializer)?;
+                Ok(match value.as_str() {
+                    $($value => $name::$variant,)*
+                    _ => $name::Unknown(value),
+                })
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value,
+                })
+            }
+        }
+    };
+}
+
 mod authenticated_get;
+mod client;
+mod config;
 pub mod create_draft;
 pub mod create_mail_folder;
 mod crypto;
+pub mod delete_draft;
 pub mod delete_mail_folder;
+pub mod delete_mails;
+pub mod delete_session;
 pub mod file;
 pub mod filedata;
+pub mod folder_tree;
+pub mod id;
 pub mod mail;
 pub mod mailbody;
 pub mod mailbox;
 pub mod mailboxgrouproot;
 pub mod mailfolder;
 pub mod move_mail;
+pub mod move_mail_folder;
 mod protocol;
+pub mod publickey;
+pub mod range;
+pub mod rename_mail_folder;
 pub mod salt;
+pub mod second_factor;
+pub mod send_draft;
 pub mod session;
+pub mod set_unread;
+pub mod totp;
+pub mod update_draft;
 pub mod update_mail;
 pub mod update_mail_folder;
 pub mod user;
 
 pub use self::crypto::*;
+pub use client::{LoginState, SecondFactorPending, SessionState, TutanotaClient};
+pub use config::Config;
 pub use protocol::Error;
@@ -2,41 +2,46 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
-use futures::Future;
+use super::{
+    id::{ConversationEntryId, ElementId, FileId, IdTuple, MailBodyId, MailId, MailListId},
+    protocol::UnknownFields,
+    range::Range,
+    Error, Key,
+};
+use futures::{Future, Stream};
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
 pub struct Mail {
     #[serde(with = "super::protocol::format")]
     _format: (),
     #[serde(rename = "_area")]
     pub area: String,
-    pub attachments: Vec<(String, String)>,
-    pub body: String,
-    // XXX What's the proper type?
+    pub attachments: Vec<FileId>,
+    pub body: MailBodyId,
     #[serde(rename = "bccRecipients")]
-    pub bcc_recipients: Vec<()>,
-    // XXX What's the proper type?
+    pub bcc_recipients: Vec<MailAddress>,
     #[serde(rename = "ccRecipients")]
-    pub cc_recipients: Vec<()>,
+    pub cc_recipients: Vec<MailAddress>,
     #[serde(with = "super::protocol::base64")]
     pub confidential: Vec<u8>,
     #[serde(rename = "conversationEntry")]
-    pub conversation_entry: (String, String),
+    pub conversation_entry: ConversationEntryId,
     // XXX What's the proper type?
     #[serde(rename = "differentEnvelopeSender")]
     pub different_envelope_sender: (),
+    /// Fields not known to this crate, kept so they are sent back unchanged when the mail is updated.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
     // XXX What's the proper type?
     pub headers: (),
     #[serde(rename = "_id")]
-    pub id: (String, String),
+    pub id: MailId,
     // XXX What's the proper type?
     #[serde(rename = "listUnsubscribe")]
     pub list_unsubscribe: String,
-    #[serde(rename = "movedTime")]
-    pub moved_time: String,
+    #[serde(rename = "movedTime", with = "super::protocol::timestamp")]
+    pub moved_time: std::time::SystemTime,
     #[serde(rename = "_owner")]
     pub owner: String,
     #[serde(with = "super::protocol::base64", rename = "_ownerEncSessionKey")]
@@ -45,50 +50,369 @@ pub struct Mail {
     pub owner_group: String,
     #[serde(rename = "_permissions")]
     pub permissions: String,
-    #[serde(rename = "receivedDate")]
-    pub received_date: String,
-    // XXX What's the proper type?
+    #[serde(rename = "receivedDate", with = "super::protocol::timestamp")]
+    pub received_date: std::time::SystemTime,
     #[serde(rename = "replyTos")]
-    pub reply_tos: Vec<()>,
-    // XXX What's the proper type?
+    pub reply_tos: Vec<EncryptedMailAddress>,
     #[serde(rename = "replyType")]
-    pub reply_type: String,
+    pub reply_type: ReplyType,
     // XXX What's the proper type?
     pub restrictions: (),
-    #[serde(rename = "sentDate")]
-    pub sent_date: String,
-    pub sender: Sender,
-    pub state: String,
+    #[serde(rename = "sentDate", with = "super::protocol::timestamp")]
+    pub sent_date: std::time::SystemTime,
+    pub sender: MailAddress,
+    pub state: MailState,
     #[serde(with = "super::protocol::base64")]
     pub subject: Vec<u8>,
     #[serde(rename = "toRecipients")]
-    pub to_recipients: Vec<Sender>,
-    pub trashed: String,
-    pub unread: String,
+    pub to_recipients: Vec<MailAddress>,
+    #[serde(with = "super::protocol::boolean")]
+    pub trashed: bool,
+    #[serde(with = "super::protocol::boolean")]
+    pub unread: bool,
 }
 
+/// A mail with its encrypted values decrypted and its other values parsed, created by `Mail::decrypt`.
+#[derive(Debug)]
+pub struct DecryptedMail {
+    pub attachments: Vec<FileId>,
+    pub bcc_recipients: Vec<DecryptedMailAddress>,
+    pub body: MailBodyId,
+    pub cc_recipients: Vec<DecryptedMailAddress>,
+    pub confidential: bool,
+    pub id: MailId,
+    pub moved_time: std::time::SystemTime,
+    pub received_date: std::time::SystemTime,
+    pub reply_tos: Vec<DecryptedMailAddress>,
+    pub reply_type: ReplyType,
+    pub sender: DecryptedMailAddress,
+    pub sent_date: std::time::SystemTime,
+    /// The session key of the mail, which the body and the attachments are encrypted with.
+    pub session_key: Key,
+    pub state: MailState,
+    pub subject: String,
+    pub to_recipients: Vec<DecryptedMailAddress>,
+    pub trashed: bool,
+    pub unread: bool,
+}
+
+/// An email address with its name decrypted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecryptedMailAddress {
+    pub address: String,
+    pub name: String,
+}
+
+string_enum! {
+    /// Whether a mail is a draft, being sent, sent or received.
+    MailState {
+        Draft = "0",
+        Sent = "1",
+        Received = "2",
+        Sending = "3",
+    }
+}
+
+string_enum! {
+    /// Whether a mail has been replied to or forwarded.
+    ReplyType {
+        None = "0",
+        Reply = "1",
+        Forward = "2",
+        ReplyForward = "3",
+    }
+}
+
+/// An email address with both the address and the name encrypted, used for reply-to addresses.
 #[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
-pub struct Sender {
+pub struct EncryptedMailAddress {
+    #[serde(with = "super::protocol::base64")]
+    pub address: Vec<u8>,
+    /// Fields not known to this crate.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(with = "super::protocol::base64")]
+    pub name: Vec<u8>,
+}
+
+impl EncryptedMailAddress {
+    /// Decrypts the address with the session key of the mail.
+    pub fn decrypt_address(&self, session_key: Key) -> Result<String, Error> {
+        super::crypto::decrypt_string(session_key, &self.address, "mail address")
+    }
+
+    /// Decrypts the name with the session key of the mail.
+    pub fn decrypt_name(&self, session_key: Key) -> Result<String, Error> {
+        super::crypto::decrypt_string(session_key, &self.name, "mail address name")
+    }
+
+    /// Decrypts the address and the name with the session key of the mail.
+    pub fn decrypt(&self, session_key: Key) -> Result<DecryptedMailAddress, Error> {
+        Ok(DecryptedMailAddress {
+            address: self.decrypt_address(session_key)?,
+            name: self.decrypt_name(session_key)?,
+        })
+    }
+}
+
+/// An email address of the sender or a recipient of a mail, with the name encrypted.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MailAddress {
     pub address: String,
-    // XXX What's the proper type?
-    pub contact: (),
+    /// The contact the address belongs to, if any.
+    pub contact: Option<IdTuple>,
+    /// Fields not known to this crate.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
     #[serde(rename = "_id")]
     pub id: String,
     #[serde(with = "super::protocol::base64")]
     pub name: Vec<u8>,
 }
 
+impl MailAddress {
+    /// Decrypts the name with the session key of the mail.
+    pub fn decrypt_name(&self, session_key: Key) -> Result<String, Error> {
+        super::crypto::decrypt_string(session_key, &self.name, "mail address name")
+    }
+
+    /// Decrypts the name with the session key of the mail, keeping the address as it is.
+    pub fn decrypt(&self, session_key: Key) -> Result<DecryptedMailAddress, Error> {
+        Ok(DecryptedMailAddress {
+            address: self.address.clone(),
+            name: self.decrypt_name(session_key)?,
+        })
+    }
+}
+
+impl Mail {
+    /// Decrypts the session key and all encrypted values of the mail and parses its dates and flags.
+    ///
+    /// Fails with `Error::Decryption` naming the value that could not be decrypted, which includes values that fail MAC verification.
+    pub fn decrypt(&self, mail_group_key: Key) -> Result<DecryptedMail, Error> {
+        let session_key = self.session_key(mail_group_key)?;
+        let decrypt_all = |addresses: &[MailAddress]| {
+            addresses
+                .iter()
+                .map(|address| address.decrypt(session_key))
+                .collect::<Result<Vec<_>, Error>>()
+        };
+        Ok(DecryptedMail {
+            attachments: self.attachments.clone(),
+            bcc_recipients: decrypt_all(&self.bcc_recipients)?,
+            body: self.body.clone(),
+            cc_recipients: decrypt_all(&self.cc_recipients)?,
+            confidential: parse_flag(
+                &super::crypto::decrypt_string(
+                    session_key,
+                    &self.confidential,
+                    "mail confidential flag",
+                )?,
+                "mail confidential flag",
+            )?,
+            id: self.id.clone(),
+            moved_time: self.moved_time,
+            received_date: self.received_date,
+            reply_tos: self
+                .reply_tos
+                .iter()
+                .map(|address| address.decrypt(session_key))
+                .collect::<Result<Vec<_>, Error>>()?,
+            reply_type: self.reply_type.clone(),
+            sender: self.sender.decrypt(session_key)?,
+            sent_date: self.sent_date,
+            session_key,
+            state: self.state.clone(),
+            subject: super::crypto::decrypt_string(session_key, &self.subject, "mail subject")?,
+            to_recipients: decrypt_all(&self.to_recipients)?,
+            trashed: self.trashed,
+            unread: self.unread,
+        })
+    }
+
+    /// Decrypts the session key of the mail, which the subject, body and other encrypted values of the mail are encrypted with.
+    pub fn session_key(&self, mail_group_key: Key) -> Result<Key, Error> {
+        super::decrypt_key(&mail_group_key, &self.owner_enc_session_key)
+            .ok_or(Error::Decryption("mail session key"))
+    }
+}
+
+impl UnknownFields for EncryptedMailAddress {
+    fn unknown_fields(&self, path: &str, output: &mut Vec<String>) {
+        super::protocol::add_unknown_fields(&self.extra, path, output);
+    }
+}
+
+impl UnknownFields for Mail {
+    fn unknown_fields(&self, path: &str, output: &mut Vec<String>) {
+        super::protocol::add_unknown_fields(&self.extra, path, output);
+        for address in &self.bcc_recipients {
+            address.unknown_fields(&format!("{}bccRecipients.", path), output);
+        }
+        for address in &self.cc_recipients {
+            address.unknown_fields(&format!("{}ccRecipients.", path), output);
+        }
+        for address in &self.reply_tos {
+            address.unknown_fields(&format!("{}replyTos.", path), output);
+        }
+        self.sender
+            .unknown_fields(&format!("{}sender.", path), output);
+        for address in &self.to_recipients {
+            address.unknown_fields(&format!("{}toRecipients.", path), output);
+        }
+    }
+}
+
+impl UnknownFields for MailAddress {
+    fn unknown_fields(&self, path: &str, output: &mut Vec<String>) {
+        super::protocol::add_unknown_fields(&self.extra, path, output);
+    }
+}
+
+impl super::range::ListElement for Mail {
+    fn element_id(&self) -> &ElementId {
+        &self.id.1
+    }
+}
+
+// Parses a decrypted flag, failing with `Error::Decryption` naming the flag if it's neither "0" nor "1".
+fn parse_flag(value: &str, name: &'static str) -> Result<bool, Error> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(Error::Decryption(name)),
+    }
+}
+
+/// Fetches the mails in the given range of the list.
 pub fn fetch_mail<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    mails: &str,
+    mails: &MailListId,
+    range: &Range,
 ) -> impl futures::Future<Error = Error, Item = Vec<Mail>> {
     let url = format!(
-        "https://mail.tutanota.com/rest/tutanota/mail/{}?start=zzzzzzzzzzzz&count=100&reverse=true",
-        mails
+        "{}/rest/tutanota/mail/{}?{}",
+        config.origin,
+        mails,
+        range.query()
     );
-    super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
-        serde_json::from_slice::<Vec<Mail>>(&response_body).map_err(Error::Format)
+    let config = config.clone();
+    super::authenticated_get::get(client, access_token, &url).and_then(move |response_body| {
+        let mails = serde_json::from_slice::<Vec<Mail>>(&response_body).map_err(Error::Format)?;
+        super::protocol::check_unknown_fields(&config, mails)
+    })
+}
+
+/// Fetches a single mail, such as a draft that was just created.
+pub fn fetch_single_mail<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    mail: &MailId,
+) -> impl futures::Future<Error = Error, Item = Mail> {
+    let url = format!("{}/rest/tutanota/mail/{}", config.origin, mail);
+    let config = config.clone();
+    super::authenticated_get::get(client, access_token, &url).and_then(move |response_body| {
+        let mail = serde_json::from_slice::<Mail>(&response_body).map_err(Error::Format)?;
+        let mut mails = super::protocol::check_unknown_fields(&config, vec![mail])?;
+        Ok(mails.remove(0))
+    })
+}
+
+/// Fetches all mails in the list from the start of the given range, lazily, one range at a time.
+pub fn stream_mail<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    mails: &MailListId,
+    range: Range,
+) -> impl Stream<Error = Error, Item = Mail> {
+    let client = client.clone();
+    let config = config.clone();
+    let access_token = access_token.to_owned();
+    let mails = mails.clone();
+    super::range::paginate(range, move |range| {
+        fetch_mail(&client, &config, &access_token, &mails, range)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Mail;
+
+    // A mail as sent by the server, with an unknown field in the mail and one in the sender.
+    const MAIL: &str = r#"{
+        "_area": "0",
+        "_format": "0",
+        "_id": ["mails", "mail"],
+        "_owner": "owner",
+        "_ownerEncSessionKey": "AAECAw==",
+        "_ownerGroup": "group",
+        "_permissions": "permissions",
+        "attachments": [],
+        "bccRecipients": [],
+        "body": "body",
+        "ccRecipients": [],
+        "confidential": "AAECAw==",
+        "conversationEntry": ["conversation", "entry"],
+        "differentEnvelopeSender": null,
+        "headers": null,
+        "listUnsubscribe": "0",
+        "movedTime": "1500000000000",
+        "receivedDate": "1500000000000",
+        "replyTos": [],
+        "replyType": "0",
+        "restrictions": null,
+        "sender": {
+            "_id": "sender",
+            "address": "alice@example.com",
+            "contact": null,
+            "name": "AAECAw==",
+            "x": [1, 2]
+        },
+        "sentDate": "1500000000000",
+        "state": "2",
+        "subject": "AAECAw==",
+        "toRecipients": [],
+        "trashed": "0",
+        "unread": "1",
+        "y": {"z": "0"}
+    }"#;
+
+    #[test]
+    fn unknown_fields_are_kept() {
+        let mail: Mail = serde_json::from_str(MAIL).unwrap();
+        assert_eq!(mail.extra.keys().collect::<Vec<_>>(), ["y"]);
+        assert_eq!(mail.extra["y"], serde_json::json!({"z": "0"}));
+        assert_eq!(mail.sender.extra.keys().collect::<Vec<_>>(), ["x"]);
+        assert_eq!(mail.sender.extra["x"], serde_json::json!([1, 2]));
+        assert_eq!(
+            serde_json::to_value(&mail).unwrap(),
+            serde_json::from_str::<serde_json::Value>(MAIL).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_fields_in_strict_mode() {
+        let mut config = super::super::Config::default();
+        let mail: Mail = serde_json::from_str(MAIL).unwrap();
+        assert_eq!(
+            super::super::protocol::check_unknown_fields(&config, vec![mail])
+                .unwrap()
+                .len(),
+            1
+        );
+        config.strict = true;
+        let mail: Mail = serde_json::from_str(MAIL).unwrap();
+        match super::super::protocol::check_unknown_fields(&config, vec![mail]) {
+            Err(super::Error::UnknownFields(unknown_fields)) => {
+                assert_eq!(unknown_fields, ["sender.x", "y"])
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
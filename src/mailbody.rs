@@ -2,7 +2,7 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{id::MailBodyId, Error};
 use futures::Future;
 use serde_derive::Deserialize;
 
@@ -16,10 +16,11 @@ pub struct Mailbody {
 
 pub fn fetch_mailbody<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    body: &str,
+    body: &MailBodyId,
 ) -> impl futures::Future<Error = Error, Item = Vec<u8>> {
-    let url = format!("https://mail.tutanota.com/rest/tutanota/mailbody/{}", body);
+    let url = format!("{}/rest/tutanota/mailbody/{}", config.origin, body);
     super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
         match serde_json::from_slice::<Mailbody>(&response_body) {
             Err(error) => Err(Error::Format(error)),
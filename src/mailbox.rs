@@ -2,7 +2,10 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{
+    id::{FolderListId, MailboxId},
+    Error,
+};
 use futures::Future;
 use serde_derive::Deserialize;
 
@@ -16,18 +19,16 @@ struct Response {
 
 #[derive(Deserialize)]
 struct SystemFolders {
-    folders: String,
+    folders: FolderListId,
 }
 
 pub fn fetch_mailbox<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    mailbox: &str,
-) -> impl hyper::rt::Future<Error = Error, Item = String> {
-    let url = format!(
-        "https://mail.tutanota.com/rest/tutanota/mailbox/{}",
-        mailbox
-    );
+    mailbox: &MailboxId,
+) -> impl hyper::rt::Future<Error = Error, Item = FolderListId> {
+    let url = format!("{}/rest/tutanota/mailbox/{}", config.origin, mailbox);
     super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
         match serde_json::from_slice::<Response>(&response_body) {
             Err(error) => Err(Error::Format(error)),
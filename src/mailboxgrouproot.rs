@@ -2,7 +2,10 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{
+    id::{GroupId, MailboxId},
+    Error,
+};
 use futures::Future;
 use serde_derive::Deserialize;
 
@@ -10,18 +13,16 @@ use serde_derive::Deserialize;
 struct Response {
     #[serde(with = "super::protocol::format")]
     _format: (),
-    mailbox: String,
+    mailbox: MailboxId,
 }
 
 pub fn fetch_mailboxgrouproot<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    group: &str,
-) -> impl futures::Future<Error = Error, Item = String> {
-    let url = format!(
-        "https://mail.tutanota.com/rest/tutanota/mailboxgrouproot/{}",
-        group
-    );
+    group: &GroupId,
+) -> impl futures::Future<Error = Error, Item = MailboxId> {
+    let url = format!("{}/rest/tutanota/mailboxgrouproot/{}", config.origin, group);
     super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
         match serde_json::from_slice::<Response>(&response_body) {
             Err(error) => Err(Error::Format(error)),
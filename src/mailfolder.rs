@@ -2,20 +2,27 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
-use futures::Future;
+use super::{
+    id::{ElementId, FolderId, FolderListId, MailListId},
+    protocol::UnknownFields,
+    range::Range,
+    Error, Key,
+};
+use futures::{Future, Stream};
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Folder {
     #[serde(rename = "folderType")]
-    pub folder_type: String,
+    pub folder_type: MailFolderType,
     #[serde(with = "super::protocol::format")]
     _format: (),
+    /// Fields not known to this crate, kept so they are sent back unchanged when the folder is updated.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
     #[serde(rename = "_id")]
-    pub id: (String, String),
-    pub mails: String,
+    pub id: FolderId,
+    pub mails: MailListId,
     #[serde(with = "super::protocol::base64")]
     pub name: Vec<u8>,
     #[serde(with = "super::protocol::base64", rename = "_ownerEncSessionKey")]
@@ -23,20 +30,104 @@ pub struct Folder {
     #[serde(rename = "_ownerGroup")]
     pub owner_group: String,
     #[serde(rename = "parentFolder")]
-    pub parent_folder: Option<(String, String)>,
+    pub parent_folder: Option<FolderId>,
     #[serde(rename = "_permissions")]
     pub permissions: String,
     #[serde(rename = "subFolders")]
-    pub sub_folders: String,
+    pub sub_folders: FolderListId,
 }
 
+string_enum! {
+    /// The purpose of a mail folder. Each mailbox has one system folder of each type except `Custom`, which is the type of folders created by the user.
+    MailFolderType {
+        Custom = "0",
+        Inbox = "1",
+        Sent = "2",
+        Trash = "3",
+        Archive = "4",
+        Spam = "5",
+        Draft = "6",
+    }
+}
+
+impl Folder {
+    /// Decrypts the session key of the folder, which the name of the folder is encrypted with.
+    pub fn session_key(&self, mail_group_key: Key) -> Result<Key, Error> {
+        super::decrypt_key(&mail_group_key, &self.owner_enc_session_key)
+            .ok_or(Error::Decryption("folder session key"))
+    }
+
+    /// Decrypts the name of the folder.
+    ///
+    /// System folders don't have a name, so this gives an empty string for them. Use `display_name` to get a name for any folder.
+    pub fn decrypted_name(&self, mail_group_key: Key) -> Result<String, Error> {
+        super::crypto::decrypt_string(self.session_key(mail_group_key)?, &self.name, "folder name")
+    }
+
+    /// The name of the folder, which is the decrypted name for a folder created by the user and the name of the folder type, such as `Inbox`, for a system folder.
+    pub fn display_name(&self, mail_group_key: Key) -> Result<String, Error> {
+        let name = match self.folder_type {
+            MailFolderType::Archive => "Archive",
+            MailFolderType::Draft => "Draft",
+            MailFolderType::Inbox => "Inbox",
+            MailFolderType::Sent => "Sent",
+            MailFolderType::Spam => "Spam",
+            MailFolderType::Trash => "Trash",
+            MailFolderType::Custom | MailFolderType::Unknown(_) => {
+                return self.decrypted_name(mail_group_key);
+            }
+        };
+        Ok(name.to_owned())
+    }
+}
+
+impl UnknownFields for Folder {
+    fn unknown_fields(&self, path: &str, output: &mut Vec<String>) {
+        super::protocol::add_unknown_fields(&self.extra, path, output);
+    }
+}
+
+impl super::range::ListElement for Folder {
+    fn element_id(&self) -> &ElementId {
+        &self.id.1
+    }
+}
+
+/// Fetches the folders in the given range of the list.
 pub fn fetch_mailfolder<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    folders: &str,
+    folders: &FolderListId,
+    range: &Range,
 ) -> impl futures::Future<Error = Error, Item = Vec<Folder>> {
-    let url = format!("https://mail.tutanota.com/rest/tutanota/mailfolder/{}?start=------------&count=1000&reverse=false", folders);
-    super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
-        serde_json::from_slice::<Vec<Folder>>(&response_body).map_err(Error::Format)
+    let url = format!(
+        "{}/rest/tutanota/mailfolder/{}?{}",
+        config.origin,
+        folders,
+        range.query()
+    );
+    let config = config.clone();
+    super::authenticated_get::get(client, access_token, &url).and_then(move |response_body| {
+        let folders =
+            serde_json::from_slice::<Vec<Folder>>(&response_body).map_err(Error::Format)?;
+        super::protocol::check_unknown_fields(&config, folders)
+    })
+}
+
+/// Fetches all folders in the list from the start of the given range, lazily, one range at a time.
+pub fn stream_mailfolder<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    folders: &FolderListId,
+    range: Range,
+) -> impl Stream<Error = Error, Item = Folder> {
+    let client = client.clone();
+    let config = config.clone();
+    let access_token = access_token.to_owned();
+    let folders = folders.clone();
+    super::range::paginate(range, move |range| {
+        fetch_mailfolder(&client, &config, &access_token, &folders, range)
     })
 }
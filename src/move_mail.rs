@@ -2,7 +2,10 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{
+    id::{FolderId, MailId},
+    Error,
+};
 use futures::Future;
 use serde_derive::Serialize;
 
@@ -10,40 +13,34 @@ use serde_derive::Serialize;
 struct Request<'a> {
     #[serde(with = "super::protocol::format")]
     _format: (),
-    mails: &'a [&'a (String, String)],
+    mails: &'a [&'a MailId],
     #[serde(rename = "targetFolder")]
-    target_folder: &'a (String, String),
+    target_folder: &'a FolderId,
 }
 
 pub fn move_mail<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    mails: &[&(String, String)],
-    target_folder: &(String, String),
+    mails: &[&MailId],
+    target_folder: &FolderId,
 ) -> impl futures::Future<Error = Error, Item = ()> {
-    let request_body = serde_json::to_string(&Request {
+    let url = format!("{}/rest/tutanota/movemailservice", config.origin);
+    let request = serde_json::to_string(&Request {
         _format: (),
         mails,
         target_folder,
     })
-    .unwrap();
-    let mut request = hyper::Request::new(hyper::Body::from(request_body));
-    *request.method_mut() = hyper::Method::POST;
-    // XXX Don't unwrap, but gracefully return error.
-    request.headers_mut().insert(
-        "accessToken",
-        hyper::header::HeaderValue::from_str(access_token).unwrap(),
-    );
-    *request.uri_mut() =
-        hyper::Uri::from_static("https://mail.tutanota.com/rest/tutanota/movemailservice");
-    client.request(request).then(|result| match result {
-        Err(error) => Err(Error::Network(error)),
-        Ok(response) => {
-            if response.status() == hyper::StatusCode::CREATED {
-                Ok(())
-            } else {
-                Err(Error::Status(response))
-            }
-        }
-    })
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        super::protocol::request(
+            hyper::Method::POST,
+            &url,
+            Some(access_token),
+            request_body.into(),
+        )
+    });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .map(|_| ())
 }
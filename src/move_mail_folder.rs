@@ -0,0 +1,134 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{
+    folder_tree::FolderNode, id::FolderId, mailfolder::MailFolderType, range::Range, Error, Key,
+};
+use futures::{
+    future::{self, Either, Loop},
+    Future, Stream,
+};
+
+// The most mails the server moves in a single request.
+const MAILS_PER_MOVE: usize = 50;
+
+/// Moves the folder and its sub folders to become a sub folder of the given parent folder, returning the new identifier of the folder.
+///
+/// The server has no operation to move a folder to another list of sub folders, so the folder and each of its descendants is created anew under its new parent, its mails are moved to the new folder, and when all mails have been moved, the original folder is deleted. The moved folders get new identifiers. If moving fails part way, no mail is lost, but some mails may have been moved to new folders while the rest remain in the original folders.
+///
+/// Fails without making any request with `Error::SystemFolder` if the folder or any of its descendants is a system folder, and with `Error::InvalidParent` if the new parent is the folder itself or one of its descendants.
+pub fn move_mail_folder<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    mail_group_key: Key,
+    folder: &FolderNode,
+    new_parent: &FolderId,
+) -> impl futures::Future<Error = Error, Item = FolderId> {
+    // The folder and its descendants, each after its parent, along with the index of its parent.
+    let mut nodes = vec![(folder, None)];
+    let mut index = 0;
+    while index < nodes.len() {
+        let node = nodes[index].0;
+        nodes.extend(node.children.iter().map(|child| (child, Some(index))));
+        index += 1;
+    }
+    if let Some((node, _)) = nodes
+        .iter()
+        .find(|(node, _)| node.folder.folder_type != MailFolderType::Custom)
+    {
+        return Either::A(future::err(Error::SystemFolder(node.folder.id.clone())));
+    }
+    if nodes.iter().any(|(node, _)| node.folder.id == *new_parent) {
+        return Either::A(future::err(Error::InvalidParent(new_parent.clone())));
+    }
+    let folders = match nodes
+        .into_iter()
+        .map(|(node, parent)| {
+            Ok((
+                node.name.clone(),
+                node.folder.mails.clone(),
+                parent,
+                super::create_key()?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()
+    {
+        Err(error) => return Either::A(future::err(error)),
+        Ok(folders) => folders,
+    };
+    let client = client.clone();
+    let config = config.clone();
+    let access_token = access_token.to_owned();
+    let new_parent = new_parent.clone();
+    let original = folder.folder.clone();
+    Either::B(
+        future::loop_fn(vec![], {
+            let client = client.clone();
+            let config = config.clone();
+            let access_token = access_token.clone();
+            move |mut created: Vec<FolderId>| {
+                let (name, mails, parent, session_key) = &folders[created.len()];
+                let parent_folder = match parent {
+                    None => &new_parent,
+                    Some(parent) => &created[*parent],
+                };
+                let client = client.clone();
+                let config = config.clone();
+                let access_token = access_token.clone();
+                let mails = mails.clone();
+                let count = folders.len();
+                super::create_mail_folder::create_mail_folder(
+                    &client,
+                    &config,
+                    &access_token,
+                    mail_group_key,
+                    *session_key,
+                    parent_folder,
+                    name,
+                )
+                .and_then(move |new_folder| {
+                    super::mail::stream_mail(
+                        &client,
+                        &config,
+                        &access_token,
+                        &mails,
+                        Range::first(1000),
+                    )
+                    .chunks(MAILS_PER_MOVE)
+                    .for_each({
+                        let new_folder = new_folder.clone();
+                        move |mails| {
+                            let mails: Vec<_> = mails.iter().map(|mail| &mail.id).collect();
+                            super::move_mail::move_mail(
+                                &client,
+                                &config,
+                                &access_token,
+                                &mails,
+                                &new_folder,
+                            )
+                        }
+                    })
+                    .map(move |()| {
+                        created.push(new_folder);
+                        if created.len() == count {
+                            Loop::Break(created.swap_remove(0))
+                        } else {
+                            Loop::Continue(created)
+                        }
+                    })
+                })
+            }
+        })
+        .and_then(move |moved_folder| {
+            super::delete_mail_folder::delete_mail_folder(
+                &client,
+                &config,
+                &access_token,
+                &original,
+            )
+            .map(move |()| moved_folder)
+        }),
+    )
+}
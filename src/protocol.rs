@@ -2,19 +2,201 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+
 /// A failure to make an HTTP request and parse the response.
 ///
-/// Some variants contain the failing `hyper::Response` so it can be inspected.
+/// Some variants contain the failing `hyper::Response` so it can be inspected. Variants for status codes known to be used by the server contain the response body.
 #[derive(Debug)]
 pub enum Error {
+    /// The server responded with status 472, the account is blocked.
+    AccessBlocked(hyper::Chunk),
+    /// The server responded with status 470, the account is deactivated.
+    AccessDeactivated(hyper::Chunk),
+    /// The server responded with status 471, the account has expired.
+    AccessExpired(hyper::Chunk),
+    /// The access token was not in the recognized format.
+    AccessToken,
+    /// The server responded with status 409, the request conflicts with the current state of the entity.
+    Conflict(hyper::Chunk),
     /// The content type of the response was not recognized.
     ContentType(hyper::Response<hyper::Body>),
+    /// A key or value could not be decrypted.
+    Decryption(&'static str),
+    /// A confidential mail can't be sent to the recipient with this email address, because the recipient doesn't have a public key.
+    ExternalRecipient(String),
     /// The format of the response body was not recognized.
     Format(serde_json::Error),
+    /// The access token could not be used as a header value.
+    InvalidHeader(hyper::header::InvalidHeaderValue),
+    /// A folder can't be moved to the folder with this identifier, because it's the folder itself or one of its descendants.
+    InvalidParent(super::id::FolderId),
+    /// The URL of the request could not be parsed, for example because an identifier contained invalid characters.
+    InvalidUri(hyper::http::uri::InvalidUri),
+    /// Writing decrypted data failed.
+    Io(std::io::Error),
+    /// The passphrase key could not be derived, for example because the key derivation function is not supported.
+    KeyDerivation(&'static str),
+    /// The server responded with status 405, the method is not allowed for the resource.
+    MethodNotAllowed(hyper::Chunk),
+    /// An entity the operation depends on was not found in the response.
+    Missing(&'static str),
     /// The HTTP request failed.
     Network(hyper::Error),
+    /// The server responded with status 401, the access token is missing or invalid.
+    NotAuthenticated(hyper::Chunk),
+    /// The server responded with status 403, the user is not allowed to access the resource.
+    NotAuthorized(hyper::Chunk),
+    /// The server responded with status 404, the resource does not exist.
+    NotFound(hyper::Chunk),
+    /// The session was not created as a persistent session, so its state can't be stored to resume it later.
+    NotPersistent,
+    /// The server responded with status 412, a precondition of the operation was not met.
+    PreconditionFailed(hyper::Chunk),
+    /// A public key was not in the recognized format.
+    PublicKey,
+    /// The random number generator of the operating system failed.
+    Rng(rand_os::rand_core::Error),
+    /// The request body could not be serialized.
+    Serialization(serde_json::Error),
+    /// The server responded with status 503, the service is temporarily unavailable.
+    ServiceUnavailable {
+        /// The time to wait before retrying, if given by the server.
+        retry_after: Option<std::time::Duration>,
+        body: hyper::Chunk,
+    },
+    /// The server responded with status 440, the session has expired.
+    SessionExpired(hyper::Chunk),
     /// The status code of the response was not recognized.
     Status(hyper::Response<hyper::Body>),
+    /// The operation is not allowed on the system folder with this identifier, such as the inbox.
+    SystemFolder(super::id::FolderId),
+    /// The server responded with status 429, too many requests have been made.
+    TooManyRequests {
+        /// The time to wait before retrying, if given by the server.
+        retry_after: Option<std::time::Duration>,
+        body: hyper::Chunk,
+    },
+    /// The response contained fields not known to this crate, given as paths such as `sender.someField`, and strict mode is enabled in the configuration.
+    UnknownFields(Vec<String>),
+}
+
+/// Passes on the response if it has the expected status code, and otherwise fails with the error corresponding to the status code.
+///
+/// For status codes known to be used by the server, the response body is read and kept in the error.
+pub fn check_status(
+    response: hyper::Response<hyper::Body>,
+    expected: hyper::StatusCode,
+) -> impl Future<Error = Error, Item = hyper::Response<hyper::Body>> {
+    let status = response.status();
+    if status == expected {
+        return Either::A(future::ok(response));
+    }
+    let create_error: fn(Option<std::time::Duration>, hyper::Chunk) -> Error = match status.as_u16()
+    {
+        401 => |_, body| Error::NotAuthenticated(body),
+        403 => |_, body| Error::NotAuthorized(body),
+        404 => |_, body| Error::NotFound(body),
+        405 => |_, body| Error::MethodNotAllowed(body),
+        409 => |_, body| Error::Conflict(body),
+        412 => |_, body| Error::PreconditionFailed(body),
+        429 => |retry_after, body| Error::TooManyRequests { retry_after, body },
+        440 => |_, body| Error::SessionExpired(body),
+        470 => |_, body| Error::AccessDeactivated(body),
+        471 => |_, body| Error::AccessExpired(body),
+        472 => |_, body| Error::AccessBlocked(body),
+        503 => |retry_after, body| Error::ServiceUnavailable { retry_after, body },
+        _ => return Either::A(future::err(Error::Status(response))),
+    };
+    let headers = response.headers();
+    let retry_after = headers
+        .get(hyper::header::RETRY_AFTER)
+        .or_else(|| headers.get("suspension-time"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(std::time::Duration::from_secs);
+    Either::B(response.into_body().concat2().then(
+        move |result| -> Result<hyper::Response<hyper::Body>, Error> {
+            Err(match result {
+                Err(error) => Error::Network(error),
+                Ok(body) => create_error(retry_after, body),
+            })
+        },
+    ))
+}
+
+/// An entity that keeps fields not known to this crate.
+pub trait UnknownFields {
+    /// Adds the names of the unknown fields of the entity and the entities it contains to the output, each prefixed with the given path.
+    fn unknown_fields(&self, path: &str, output: &mut Vec<String>);
+}
+
+/// Adds the names of the fields in the map to the output, each prefixed with the given path.
+pub fn add_unknown_fields(
+    extra: &serde_json::Map<String, serde_json::Value>,
+    path: &str,
+    output: &mut Vec<String>,
+) {
+    output.extend(extra.keys().map(|name| format!("{}{}", path, name)));
+}
+
+/// Passes on the entities, or fails with `Error::UnknownFields` if strict mode is enabled and any of them has unknown fields.
+pub fn check_unknown_fields<T: UnknownFields>(
+    config: &super::Config,
+    entities: Vec<T>,
+) -> Result<Vec<T>, Error> {
+    if config.strict {
+        let mut unknown_fields = vec![];
+        for entity in &entities {
+            entity.unknown_fields("", &mut unknown_fields);
+        }
+        if !unknown_fields.is_empty() {
+            unknown_fields.sort();
+            unknown_fields.dedup();
+            return Err(Error::UnknownFields(unknown_fields));
+        }
+    }
+    Ok(entities)
+}
+
+/// Creates a random identifier for an entity aggregated in another entity, such as a recipient of a mail.
+pub fn aggregate_id() -> Result<String, Error> {
+    let mut bytes = [0; 4];
+    super::crypto::random_bytes(&mut bytes)?;
+    Ok(::base64::encode_config(&bytes, ::base64::URL_SAFE_NO_PAD))
+}
+
+/// Creates a request, authenticated with the access token if one is given.
+pub fn request(
+    method: hyper::Method,
+    url: &str,
+    access_token: Option<&str>,
+    body: hyper::Body,
+) -> Result<hyper::Request<hyper::Body>, Error> {
+    let mut request = hyper::Request::new(body);
+    *request.method_mut() = method;
+    if let Some(access_token) = access_token {
+        request.headers_mut().insert(
+            "accessToken",
+            hyper::header::HeaderValue::from_str(access_token).map_err(Error::InvalidHeader)?,
+        );
+    }
+    *request.uri_mut() = url.parse().map_err(Error::InvalidUri)?;
+    Ok(request)
+}
+
+/// Sends the request, or fails immediately if the request could not be created.
+pub fn send<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    request: Result<hyper::Request<hyper::Body>, Error>,
+) -> impl Future<Error = Error, Item = hyper::Response<hyper::Body>> {
+    match request {
+        Err(error) => Either::A(future::err(error)),
+        Ok(request) => Either::B(client.request(request).map_err(Error::Network)),
+    }
 }
 
 pub mod base64 {
@@ -48,6 +230,81 @@ pub mod base64 {
     }
 }
 
+pub mod boolean {
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<bool, D::Error> {
+        deserializer.deserialize_str(BooleanVisitor)
+    }
+
+    struct BooleanVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for BooleanVisitor {
+        type Value = bool;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(formatter, "string \"0\" or \"1\"")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            match value {
+                "0" => Ok(false),
+                "1" => Ok(true),
+                _ => Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Str(value),
+                    &self,
+                )),
+            }
+        }
+    }
+
+    pub fn serialize<S: serde::Serializer>(value: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if *value { "1" } else { "0" })
+    }
+}
+
+/// A point in time, represented by the server as a string of the number of milliseconds since the Unix epoch.
+pub mod timestamp {
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<std::time::SystemTime, D::Error> {
+        deserializer.deserialize_str(TimestampVisitor)
+    }
+
+    struct TimestampVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for TimestampVisitor {
+        type Value = std::time::SystemTime;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(formatter, "string of milliseconds since the Unix epoch")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            value
+                .parse()
+                .ok()
+                .and_then(|milliseconds| {
+                    std::time::UNIX_EPOCH
+                        .checked_add(std::time::Duration::from_millis(milliseconds))
+                })
+                .ok_or_else(|| {
+                    serde::de::Error::invalid_value(serde::de::Unexpected::Str(value), &self)
+                })
+        }
+    }
+
+    pub fn serialize<S: serde::Serializer>(
+        value: &std::time::SystemTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let duration = value
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| <S::Error as serde::ser::Error>::custom("time before the Unix epoch"))?;
+        serializer.collect_str(&(duration.as_secs() * 1000 + u64::from(duration.subsec_millis())))
+    }
+}
+
 pub mod format {
     pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<(), D::Error> {
         deserializer.deserialize_str(FormatVisitor)
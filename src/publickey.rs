@@ -0,0 +1,64 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::Error;
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+use serde_derive::Deserialize;
+
+/// The public key of a user, used to encrypt keys for the user.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKey {
+    #[serde(rename = "_format", with = "super::protocol::format")]
+    _format: (),
+    #[serde(with = "super::protocol::base64")]
+    pub pub_key: Vec<u8>,
+    pub pub_key_version: String,
+}
+
+/// Fetches the current public key of the user with the given email address.
+///
+/// Fails with `Error::NotFound` if the email address doesn't belong to a user of the server.
+pub fn fetch_publickey<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    email_address: &str,
+) -> impl futures::Future<Error = Error, Item = PublicKey> {
+    let request = serde_json::to_string(email_address)
+        .map_err(Error::Serialization)
+        .and_then(|email_address| {
+            let url = format!(
+                "{}/rest/sys/publickeyservice?_body=%7B%22_format%22%3A%220%22%2C%22mailAddress%22%3A{}%2C%22version%22%3Anull%7D",
+                config.origin,
+                urlencoding::encode(&email_address)
+            );
+            super::protocol::request(
+                hyper::Method::GET,
+                &url,
+                Some(access_token),
+                Default::default(),
+            )
+        });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
+                None => true,
+                Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
+            } {
+                Either::A(future::err(Error::ContentType(response)))
+            } else {
+                Either::B(response.into_body().concat2().then(|result| match result {
+                    Err(error) => Err(Error::Network(error)),
+                    Ok(response_body) => {
+                        serde_json::from_slice(&response_body).map_err(Error::Format)
+                    }
+                }))
+            }
+        })
+}
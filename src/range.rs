@@ -0,0 +1,133 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{id::ElementId, Error};
+use futures::{stream, Future, Stream};
+
+/// A range of entities in a list.
+#[derive(Clone, Debug)]
+pub struct Range {
+    /// The element identifier to start from. The entity with this identifier is not included.
+    pub start: ElementId,
+    /// The maximum number of entities.
+    pub count: u32,
+    /// Whether to walk the list from newer to older entities.
+    pub reverse: bool,
+}
+
+impl Range {
+    /// The oldest entities of a list.
+    pub fn first(count: u32) -> Self {
+        Range {
+            start: ElementId::min(),
+            count,
+            reverse: false,
+        }
+    }
+
+    /// The newest entities of a list, from newer to older.
+    pub fn last(count: u32) -> Self {
+        Range {
+            start: ElementId::max(),
+            count,
+            reverse: true,
+        }
+    }
+
+    pub(crate) fn query(&self) -> String {
+        format!(
+            "start={}&count={}&reverse={}",
+            urlencoding::encode(&self.start.0),
+            self.count,
+            self.reverse
+        )
+    }
+}
+
+/// An entity stored in a list.
+pub(crate) trait ListElement {
+    fn element_id(&self) -> &ElementId;
+}
+
+/// Walks a list by fetching one range after another, each starting after the last entity of the previous one, until a range is not full.
+///
+/// Nothing is fetched if the count of the range is zero.
+pub(crate) fn paginate<T, F, R>(range: Range, mut fetch: F) -> impl Stream<Error = Error, Item = T>
+where
+    T: ListElement,
+    F: FnMut(&Range) -> R,
+    R: Future<Error = Error, Item = Vec<T>>,
+{
+    stream::unfold(Some(range).filter(|range| range.count > 0), move |range| {
+        range.map(|range| {
+            fetch(&range).map(move |page| {
+                let next = match page.last() {
+                    Some(last) if page.len() as u32 >= range.count => Some(Range {
+                        start: last.element_id().clone(),
+                        ..range
+                    }),
+                    _ => None,
+                };
+                (stream::iter_ok::<_, Error>(page), next)
+            })
+        })
+    })
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::id::ElementId, ListElement, Range};
+    use futures::{future, Future, Stream};
+
+    struct Element(ElementId);
+
+    impl ListElement for Element {
+        fn element_id(&self) -> &ElementId {
+            &self.0
+        }
+    }
+
+    // Walks a list of the given length, giving the walked elements and the start of each range fetched.
+    fn paginate(length: usize, count: u32) -> (Vec<String>, Vec<String>) {
+        let list: Vec<_> = (0..length).map(|index| format!("{:02}", index)).collect();
+        let mut starts = vec![];
+        let elements = super::paginate(Range::first(count), |range| {
+            starts.push(range.start.0.clone());
+            let page = list
+                .iter()
+                .filter(|id| **id > range.start.0)
+                .take(range.count as usize)
+                .map(|id| Element(ElementId(id.clone())))
+                .collect();
+            future::ok(page)
+        })
+        .map(|Element(id)| id.0)
+        .collect()
+        .wait()
+        .unwrap();
+        (elements, starts)
+    }
+
+    #[test]
+    fn paginate_full_last_page() {
+        let (elements, starts) = paginate(4, 2);
+        assert_eq!(elements, ["00", "01", "02", "03"]);
+        assert_eq!(starts, ["------------", "01", "03"]);
+    }
+
+    #[test]
+    fn paginate_partial_last_page() {
+        let (elements, starts) = paginate(5, 2);
+        assert_eq!(elements, ["00", "01", "02", "03", "04"]);
+        assert_eq!(starts, ["------------", "01", "03"]);
+    }
+
+    #[test]
+    fn paginate_zero_count() {
+        let (elements, starts) = paginate(4, 0);
+        assert!(elements.is_empty());
+        assert!(starts.is_empty());
+    }
+}
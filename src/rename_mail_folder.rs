@@ -0,0 +1,33 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{mailfolder::Folder, Error, Key};
+use futures::future::{self, Either};
+
+/// Renames the folder, encrypting the new name with the existing session key of the folder.
+pub fn rename_mail_folder<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    mail_group_key: Key,
+    folder: &Folder,
+    name: &str,
+) -> impl futures::Future<Error = Error, Item = ()> {
+    let name = folder.session_key(mail_group_key).and_then(|session_key| {
+        super::encrypt_with_mac(&super::SubKeys::new(session_key), name.as_bytes())
+    });
+    match name {
+        Err(error) => Either::A(future::err(error)),
+        Ok(name) => {
+            let mut folder = folder.clone();
+            folder.name = name;
+            Either::B(super::update_mail_folder::update_mail_folder(
+                client,
+                config,
+                access_token,
+                &folder,
+            ))
+        }
+    }
+}
@@ -9,47 +9,81 @@ use futures::{
 };
 use serde_derive::Deserialize;
 
-#[derive(Deserialize)]
-struct Response {
-    #[serde(with = "super::protocol::format")]
+string_enum! {
+    /// The key derivation function used to derive the passphrase key of a user.
+    KdfType {
+        Bcrypt = "0",
+        Argon2id = "1",
+    }
+}
+
+/// The salt of the passphrase key of a user, along with the key derivation function it's used with.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Salt {
+    #[serde(rename = "_format", with = "super::protocol::format")]
     _format: (),
+    // Absent for users whose passphrase key predates Argon2id.
+    #[serde(default = "default_kdf_type", rename = "kdfVersion")]
+    pub kdf_type: KdfType,
     #[serde(with = "super::protocol::base64")]
-    salt: Vec<u8>,
+    pub salt: Vec<u8>,
+}
+
+fn default_kdf_type() -> KdfType {
+    KdfType::Bcrypt
 }
 
 pub fn fetch_salt<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     email_address: &str,
-) -> impl futures::Future<Error = Error, Item = Vec<u8>> {
-    let email_address = serde_json::to_string(email_address).unwrap();
-    let email_address = urlencoding::encode(&email_address);
-    let url = format!(
-        "https://mail.tutanota.com/rest/sys/saltservice?_body=%7B%22_format%22%3A%220%22%2C%22mailAddress%22%3A{}%7D",
-        email_address
-    );
-    client
-        .get(url.parse().unwrap())
-        .then(|result| match result {
-            Err(error) => Either::A(future::err(Error::Network(error))),
-            Ok(response) => {
-                if response.status() != hyper::StatusCode::OK {
-                    Either::A(future::err(Error::Status(response)))
-                } else if match response.headers().get(hyper::header::CONTENT_TYPE) {
-                    None => true,
-                    Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
-                } {
-                    Either::A(future::err(Error::ContentType(response)))
-                } else {
-                    Either::B(response.into_body().concat2().then(|result| match result {
-                        Err(error) => Err(Error::Network(error)),
-                        Ok(response_body) => {
-                            match serde_json::from_slice::<Response>(&response_body) {
-                                Err(error) => Err(Error::Format(error)),
-                                Ok(response_data) => Ok(response_data.salt),
-                            }
-                        }
-                    }))
-                }
+) -> impl futures::Future<Error = Error, Item = Salt> {
+    let request = serde_json::to_string(email_address)
+        .map_err(Error::Serialization)
+        .and_then(|email_address| {
+            let url = format!(
+                "{}/rest/sys/saltservice?_body=%7B%22_format%22%3A%220%22%2C%22mailAddress%22%3A{}%7D",
+                config.origin,
+                urlencoding::encode(&email_address)
+            );
+            super::protocol::request(hyper::Method::GET, &url, None, Default::default())
+        });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
+                None => true,
+                Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
+            } {
+                Either::A(future::err(Error::ContentType(response)))
+            } else {
+                Either::B(response.into_body().concat2().then(|result| match result {
+                    Err(error) => Err(Error::Network(error)),
+                    Ok(response_body) => {
+                        serde_json::from_slice(&response_body).map_err(Error::Format)
+                    }
+                }))
             }
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{KdfType, Salt};
+
+    #[test]
+    fn deserialize_with_kdf_type() {
+        let salt: Salt =
+            serde_json::from_str(r#"{"_format":"0","kdfVersion":"1","salt":"AAECAw=="}"#).unwrap();
+        assert_eq!(salt.kdf_type, KdfType::Argon2id);
+        assert_eq!(salt.salt, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn deserialize_without_kdf_type() {
+        let salt: Salt = serde_json::from_str(r#"{"_format":"0","salt":"AAECAw=="}"#).unwrap();
+        assert_eq!(salt.kdf_type, KdfType::Bcrypt);
+        assert_eq!(salt.salt, [0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,43 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{id::SessionId, session::ChallengeType, Error};
+use futures::Future;
+use serde_derive::Serialize;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Request<'a> {
+    #[serde(rename = "_format", with = "super::protocol::format")]
+    format: (),
+    otp_code: &'a str,
+    session: &'a SessionId,
+    #[serde(rename = "type")]
+    second_factor_type: ChallengeType,
+    u2f: (),
+}
+
+/// Authenticates a session that is waiting for a second factor, using a time-based one-time password.
+pub fn authenticate_totp<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    session: &SessionId,
+    code: &str,
+) -> impl futures::Future<Error = Error, Item = ()> {
+    let url = format!("{}/rest/sys/secondfactorauthservice", config.origin);
+    let request = serde_json::to_string(&Request {
+        format: (),
+        otp_code: code,
+        session,
+        second_factor_type: ChallengeType::Totp,
+        u2f: (),
+    })
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        super::protocol::request(hyper::Method::POST, &url, None, request_body.into())
+    });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .map(|_| ())
+}
@@ -0,0 +1,240 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{
+    id::{FileId, MailId},
+    mail::Mail,
+    publickey::PublicKey,
+    Error, Key,
+};
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+use serde_derive::{Deserialize, Serialize};
+
+/// A file attached to the draft, with the session key the file is encrypted with.
+pub struct Attachment<'a> {
+    pub file: &'a FileId,
+    pub session_key: Key,
+}
+
+pub struct SendDraftData<'a> {
+    pub attachments: &'a [Attachment<'a>],
+    /// Whether the mail is end-to-end encrypted for the recipients. Otherwise the session key of the mail is sent to the server, which is required for recipients not using Tutanota.
+    pub confidential: bool,
+    /// The draft as stored on the server, which can be fetched with `mail::fetch_single_mail`. The mail is sent to its recipients, including carbon copy and blind carbon copy recipients.
+    pub draft: &'a Mail,
+    /// The language code of the notification sent to recipients not using Tutanota, for example `en`.
+    pub language: &'a str,
+    pub sender_name: &'a str,
+    /// The session key the draft was created with.
+    pub session_key: Key,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AttachmentKeyData<'a> {
+    bucket_enc_file_session_key: Option<String>,
+    file: &'a FileId,
+    file_session_key: Option<String>,
+    #[serde(rename = "_id")]
+    id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InternalRecipientKeyData {
+    #[serde(rename = "_id")]
+    id: String,
+    mail_address: String,
+    #[serde(with = "super::protocol::base64")]
+    pub_enc_bucket_key: Vec<u8>,
+    pub_key_version: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Request<'a> {
+    #[serde(rename = "_format", with = "super::protocol::format")]
+    format: (),
+    attachment_key_data: Vec<AttachmentKeyData<'a>>,
+    bucket_enc_mail_session_key: Option<String>,
+    internal_recipient_key_data: Vec<InternalRecipientKeyData>,
+    language: &'a str,
+    mail: &'a MailId,
+    mail_session_key: Option<String>,
+    #[serde(with = "super::protocol::boolean")]
+    plaintext: bool,
+    // Always empty, because sending confidential mail to recipients not using Tutanota, which requires a password shared with each recipient, is not supported.
+    secure_external_recipient_key_data: [(); 0],
+    sender_name_unencrypted: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    #[serde(rename = "_format", with = "super::protocol::format")]
+    _format: (),
+    sent_mail: MailId,
+}
+
+/// Sends a draft to its recipients, returning the identifier of the sent mail.
+///
+/// For a confidential mail, the public key of each recipient is fetched and the session key of the mail is encrypted for each recipient through a bucket key. This fails with `Error::ExternalRecipient` if a recipient doesn't have a public key, because sending confidential mail to recipients not using Tutanota is not supported. That would require a password shared with the recipient, from which a key for the recipient is derived and given to the server as secure external recipient key data.
+pub fn send_draft<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    send_draft_data: SendDraftData,
+) -> impl futures::Future<Error = Error, Item = MailId> {
+    let recipients: Vec<String> = send_draft_data
+        .draft
+        .to_recipients
+        .iter()
+        .chain(&send_draft_data.draft.cc_recipients)
+        .chain(&send_draft_data.draft.bcc_recipients)
+        .map(|recipient| recipient.address.clone())
+        .collect();
+    let public_keys_future = if send_draft_data.confidential {
+        Either::A(future::join_all(
+            recipients
+                .iter()
+                .map(|recipient| {
+                    super::publickey::fetch_publickey(client, config, access_token, recipient).then(
+                        |result| match result {
+                            Err(Error::NotFound(_)) => Ok(None),
+                            Err(error) => Err(error),
+                            Ok(public_key) => Ok(Some(public_key)),
+                        },
+                    )
+                })
+                .collect::<Vec<_>>(),
+        ))
+    } else {
+        Either::B(future::ok(Vec::new()))
+    };
+    let client = client.clone();
+    let url = format!("{}/rest/tutanota/senddraftservice", config.origin);
+    let access_token = access_token.to_owned();
+    let attachments: Vec<(FileId, Key)> = send_draft_data
+        .attachments
+        .iter()
+        .map(|attachment| (attachment.file.clone(), attachment.session_key))
+        .collect();
+    let confidential = send_draft_data.confidential;
+    let draft = send_draft_data.draft.id.clone();
+    let language = send_draft_data.language.to_owned();
+    let sender_name = send_draft_data.sender_name.to_owned();
+    let session_key = send_draft_data.session_key;
+    public_keys_future
+        .and_then(move |public_keys: Vec<Option<PublicKey>>| {
+            let request = create_request(
+                &attachments,
+                confidential,
+                &draft,
+                &language,
+                recipients,
+                public_keys,
+                &sender_name,
+                session_key,
+            )
+            .and_then(|request_body| {
+                super::protocol::request(
+                    hyper::Method::POST,
+                    &url,
+                    Some(&access_token),
+                    request_body.into(),
+                )
+            });
+            super::protocol::send(&client, request)
+        })
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
+                None => true,
+                Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
+            } {
+                Either::A(future::err(Error::ContentType(response)))
+            } else {
+                Either::B(response.into_body().concat2().then(|result| match result {
+                    Err(error) => Err(Error::Network(error)),
+                    Ok(response_body) => match serde_json::from_slice::<Response>(&response_body) {
+                        Err(error) => Err(Error::Format(error)),
+                        Ok(response_data) => Ok(response_data.sent_mail),
+                    },
+                }))
+            }
+        })
+}
+
+fn create_request(
+    attachments: &[(FileId, Key)],
+    confidential: bool,
+    draft: &MailId,
+    language: &str,
+    recipients: Vec<String>,
+    public_keys: Vec<Option<PublicKey>>,
+    sender_name: &str,
+    session_key: Key,
+) -> Result<String, Error> {
+    let mut attachment_key_data = Vec::with_capacity(attachments.len());
+    let mut internal_recipient_key_data = Vec::with_capacity(public_keys.len());
+    let (bucket_enc_mail_session_key, mail_session_key) = if confidential {
+        let bucket_key = super::create_key()?;
+        for (file, file_session_key) in attachments {
+            attachment_key_data.push(AttachmentKeyData {
+                bucket_enc_file_session_key: Some(base64::encode(&super::encrypt_key(
+                    bucket_key,
+                    *file_session_key,
+                )?)),
+                file,
+                file_session_key: None,
+                id: super::protocol::aggregate_id()?,
+            });
+        }
+        for (mail_address, public_key) in recipients.into_iter().zip(public_keys) {
+            let public_key = match public_key {
+                None => return Err(Error::ExternalRecipient(mail_address)),
+                Some(public_key) => public_key,
+            };
+            internal_recipient_key_data.push(InternalRecipientKeyData {
+                id: super::protocol::aggregate_id()?,
+                mail_address,
+                pub_enc_bucket_key: super::rsa_encrypt(&public_key.pub_key, bucket_key.as_bytes())?,
+                pub_key_version: public_key.pub_key_version,
+            });
+        }
+        (
+            Some(base64::encode(&super::encrypt_key(
+                bucket_key,
+                session_key,
+            )?)),
+            None,
+        )
+    } else {
+        for (file, file_session_key) in attachments {
+            attachment_key_data.push(AttachmentKeyData {
+                bucket_enc_file_session_key: None,
+                file,
+                file_session_key: Some(base64::encode(file_session_key.as_bytes())),
+                id: super::protocol::aggregate_id()?,
+            });
+        }
+        (None, Some(base64::encode(session_key.as_bytes())))
+    };
+    serde_json::to_string(&Request {
+        format: (),
+        attachment_key_data,
+        bucket_enc_mail_session_key,
+        internal_recipient_key_data,
+        language,
+        mail: draft,
+        mail_session_key,
+        plaintext: !confidential,
+        secure_external_recipient_key_data: [],
+        sender_name_unencrypted: sender_name,
+    })
+    .map_err(Error::Serialization)
+}
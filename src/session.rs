@@ -2,7 +2,10 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{
+    id::{ElementId, ListId, SessionId, UserId},
+    Error, Key,
+};
 use futures::{
     future::{self, Either},
     Future, Stream,
@@ -13,7 +16,7 @@ use sha2::Digest;
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct Request<'a> {
-    access_key: (),
+    access_key: Option<String>,
     auth_token: (),
     auth_verifier: String,
     client_identifier: &'a str,
@@ -24,27 +27,76 @@ struct Request<'a> {
     user: (),
 }
 
+string_enum! {
+    /// A kind of second factor that can be used to authenticate a session.
+    ChallengeType {
+        U2f = "0",
+        Totp = "1",
+    }
+}
+
+/// A second factor that must be given before the session can be used.
+#[derive(Debug, Deserialize)]
+pub struct Challenge {
+    #[serde(rename = "type")]
+    pub challenge_type: ChallengeType,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Response {
     #[serde(with = "super::protocol::format")]
     _format: (),
     #[serde(rename = "accessToken")]
     pub access_token: String,
-    pub user: String,
+    /// The second factors of which one must be given before the session can be used. Empty if the user doesn't have second factors.
+    pub challenges: Vec<Challenge>,
+    pub user: UserId,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    #[serde(rename = "_format", with = "super::protocol::format")]
+    _format: (),
+    access_key: Option<String>,
 }
 
+/// Fetches the access key that was given when the session was created, which also verifies that the session is still valid.
+pub fn fetch_access_key<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    session: &SessionId,
+) -> impl futures::Future<Error = Error, Item = Key> {
+    let url = format!("{}/rest/sys/session/{}", config.origin, session);
+    super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
+        let session = serde_json::from_slice::<Session>(&response_body).map_err(Error::Format)?;
+        session
+            .access_key
+            .and_then(|access_key| base64::decode(&access_key).ok())
+            .and_then(|access_key| Key::from_bytes(&access_key))
+            .ok_or(Error::Missing("session access key"))
+    })
+}
+
+/// Creates a session, authenticated with the key derived from the passphrase of the user.
+///
+/// If an access key is given, the session is persistent and the access key is stored with it, so it can be fetched with `fetch_access_key` and values encrypted with it can be decrypted when the session is resumed. Without an access key, the session is not persistent.
 pub fn fetch_session<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_key: Option<Key>,
     client_identifier: &str,
     email_address: &str,
-    user_passphrase_key: &[u8],
+    user_passphrase_key: &Key,
 ) -> impl hyper::rt::Future<Error = Error, Item = Response> {
     let mut hasher = sha2::Sha256::new();
-    hasher.input(user_passphrase_key);
+    hasher.input(user_passphrase_key.as_bytes());
     let hash = hasher.result();
     let auth_verifier = base64::encode_config(&hash, base64::URL_SAFE_NO_PAD);
-    let request_body = serde_json::to_string(&Request {
-        access_key: (),
+    let url = format!("{}/rest/sys/sessionservice", config.origin);
+    let request = serde_json::to_string(&Request {
+        access_key: access_key.map(|access_key| base64::encode(access_key.as_bytes())),
         auth_token: (),
         auth_verifier,
         client_identifier,
@@ -53,17 +105,14 @@ pub fn fetch_session<C: 'static + hyper::client::connect::Connect>(
         recover_code_verifier: (),
         user: (),
     })
-    .unwrap();
-    let mut request = hyper::Request::new(hyper::Body::from(request_body));
-    *request.method_mut() = hyper::Method::POST;
-    *request.uri_mut() =
-        hyper::Uri::from_static("https://mail.tutanota.com/rest/sys/sessionservice");
-    client.request(request).then(|result| match result {
-        Err(error) => Either::A(future::err(Error::Network(error))),
-        Ok(response) => {
-            if response.status() != hyper::StatusCode::CREATED {
-                Either::A(future::err(Error::Status(response)))
-            } else if match response.headers().get(hyper::header::CONTENT_TYPE) {
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        super::protocol::request(hyper::Method::POST, &url, None, request_body.into())
+    });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
                 None => true,
                 Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
             } {
@@ -76,6 +125,80 @@ pub fn fetch_session<C: 'static + hyper::client::connect::Connect>(
                     }
                 }))
             }
+        })
+}
+
+/// Derives the identifier of the session from its access token.
+///
+/// The access token consists of the identifier of the list of sessions followed by a secret. The element identifier of the session is the hash of the secret.
+pub fn session_id(access_token: &str) -> Result<SessionId, Error> {
+    const LIST_ID_LENGTH: usize = 9;
+    let access_token = base64::decode_config(access_token, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| Error::AccessToken)?;
+    if access_token.len() <= LIST_ID_LENGTH {
+        return Err(Error::AccessToken);
+    }
+    let mut hasher = sha2::Sha256::new();
+    hasher.input(&access_token[LIST_ID_LENGTH..]);
+    Ok(super::id::IdTuple(
+        ListId(encode_base64_ext(&access_token[..LIST_ID_LENGTH])),
+        ElementId(base64::encode_config(
+            &hasher.result(),
+            base64::URL_SAFE_NO_PAD,
+        )),
+    ))
+}
+
+// Encodes with the alphabet of generated identifiers, which is like base64 but sorted in the same order as the encoded bytes.
+fn encode_base64_ext(input: &[u8]) -> String {
+    const STANDARD: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    const EXT: &[u8] = b"-0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz";
+    base64::encode_config(input, base64::STANDARD_NO_PAD)
+        .bytes()
+        .map(|byte| {
+            let index = STANDARD.iter().position(|&other| other == byte).unwrap();
+            char::from(EXT[index])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::id::{ElementId, IdTuple, ListId};
+
+    #[test]
+    fn session_id() {
+        for &(access_token, list_id, element_id) in &[
+            (
+                "AAECAwQFBgcICQoLDA0ODxAREhMUFRYX",
+                "--31-kF40VR7",
+                "IIqM3g_AsjTPc3TRZ9gSwDmmMbsG63IMb8SA6yEA3RQ",
+            ),
+            (
+                "-_-_-_-_-_-_c2VjcmV0LXBhcnQ",
+                "yzyzyzyzyzyz",
+                "ZtCO71MvPKj_vviRf5D8YlJUK464qHklY5BiUBRx5Cw",
+            ),
+        ] {
+            assert_eq!(
+                super::session_id(access_token).unwrap(),
+                IdTuple(ListId(list_id.into()), ElementId(element_id.into()))
+            );
         }
-    })
+    }
+
+    #[test]
+    fn session_id_rejects_invalid_access_tokens() {
+        for access_token in &[
+            "",
+            "AAECAwQFBgcI",
+            "AAECAwQFBgcICQoL+/",
+            "AAECAwQFBgcICQoL=",
+        ] {
+            match super::session_id(access_token) {
+                Err(super::Error::AccessToken) => {}
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
 }
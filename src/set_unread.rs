@@ -0,0 +1,44 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{id::MailId, Error};
+use futures::Future;
+use serde_derive::Serialize;
+
+#[derive(Serialize)]
+struct Request<'a> {
+    #[serde(with = "super::protocol::format")]
+    _format: (),
+    mails: &'a [&'a MailId],
+    #[serde(with = "super::protocol::boolean")]
+    unread: bool,
+}
+
+/// Marks the mails as unread or read, without updating anything else.
+pub fn set_unread<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    mails: &[&MailId],
+    unread: bool,
+) -> impl futures::Future<Error = Error, Item = ()> {
+    let url = format!("{}/rest/tutanota/unreadmailstateservice", config.origin);
+    let request = serde_json::to_string(&Request {
+        _format: (),
+        mails,
+        unread,
+    })
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        super::protocol::request(
+            hyper::Method::POST,
+            &url,
+            Some(access_token),
+            request_body.into(),
+        )
+    });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::CREATED))
+        .map(|_| ())
+}
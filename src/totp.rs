@@ -0,0 +1,146 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+//! Time-based one-time passwords as defined in RFC 6238, as used for second factor authentication.
+//!
+//! The passwords have six digits and change every 30 seconds. The secret is usually given to the user encoded in base32, which can be decoded with `decode_secret`.
+
+const DIGITS: u32 = 6;
+const TIME_STEP: u64 = 30;
+
+/// Decodes a secret given in base32, ignoring spaces and case.
+pub fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(secret.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for character in secret.chars() {
+        let value = match character.to_ascii_uppercase() {
+            ' ' | '=' => continue,
+            character @ 'A'..='Z' => character as u32 - 'A' as u32,
+            character @ '2'..='7' => character as u32 - '2' as u32 + 26,
+            _ => return None,
+        };
+        buffer = buffer << 5 | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(output)
+}
+
+/// Generates the password for the time.
+pub fn generate(secret: &[u8], time: std::time::SystemTime) -> String {
+    format!(
+        "{:01$}",
+        generate_for_counter(secret, counter(time)),
+        DIGITS as usize
+    )
+}
+
+/// Checks whether the password is valid at the time, also accepting the passwords of the previous and the next time step to allow for clocks being out of sync.
+pub fn verify(secret: &[u8], password: &str, time: std::time::SystemTime) -> bool {
+    let counter = counter(time);
+    password.len() == DIGITS as usize
+        && password.bytes().all(|byte| byte.is_ascii_digit())
+        && password.parse().ok().map_or(false, |password: u32| {
+            (counter.saturating_sub(1)..=counter + 1)
+                .any(|counter| generate_for_counter(secret, counter) == password)
+        })
+}
+
+fn counter(time: std::time::SystemTime) -> u64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() / TIME_STEP)
+        .unwrap_or(0)
+}
+
+// Generates a password as defined for HOTP in RFC 4226, using HMAC-SHA-1.
+fn generate_for_counter(secret: &[u8], counter: u64) -> u32 {
+    use crypto::mac::Mac;
+    let mut mac = crypto::hmac::Hmac::new(crypto::sha1::Sha1::new(), secret);
+    mac.input(&counter.to_be_bytes());
+    let hash = mac.result();
+    let hash = hash.code();
+    let offset = usize::from(hash[hash.len() - 1] & 0xf);
+    let value = u32::from(hash[offset] & 0x7f) << 24
+        | u32::from(hash[offset + 1]) << 16
+        | u32::from(hash[offset + 2]) << 8
+        | u32::from(hash[offset + 3]);
+    value % 10u32.pow(DIGITS)
+}
+
+#[cfg(test)]
+mod tests {
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    fn time(seconds: u64) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds)
+    }
+
+    // The SHA-1 test vectors of RFC 6238 Appendix B, which have eight digits, truncated to six digits.
+    #[test]
+    fn generate_rfc_6238_vectors() {
+        for &(seconds, password) in &[
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_111_111_111, "050471"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+            (20_000_000_000, "353130"),
+        ] {
+            assert_eq!(super::generate(SECRET, time(seconds)), password);
+        }
+    }
+
+    #[test]
+    fn verify_adjacent_time_steps() {
+        let now = time(1_111_111_111);
+        assert!(super::verify(SECRET, "050471", now));
+        assert!(super::verify(
+            SECRET,
+            &super::generate(SECRET, time(1_111_111_111 - 30)),
+            now
+        ));
+        assert!(super::verify(
+            SECRET,
+            &super::generate(SECRET, time(1_111_111_111 + 30)),
+            now
+        ));
+        assert!(!super::verify(
+            SECRET,
+            &super::generate(SECRET, time(1_111_111_111 - 60)),
+            now
+        ));
+        assert!(!super::verify(
+            SECRET,
+            &super::generate(SECRET, time(1_111_111_111 + 60)),
+            now
+        ));
+        assert!(!super::verify(SECRET, "50471", now));
+        assert!(!super::verify(SECRET, "+50471", now));
+    }
+
+    #[test]
+    fn decode_secret() {
+        let expected = Some(SECRET.to_vec());
+        assert_eq!(
+            super::decode_secret("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"),
+            expected
+        );
+        assert_eq!(
+            super::decode_secret("gezdgnbvgy3tqojqgezdgnbvgy3tqojq"),
+            expected
+        );
+        assert_eq!(
+            super::decode_secret("GEZD GNBV GY3T QOJQ GEZD GNBV GY3T QOJQ"),
+            expected
+        );
+        assert_eq!(super::decode_secret("MZXW6==="), Some(b"foo".to_vec()));
+        assert_eq!(super::decode_secret("mzxw 6yq="), Some(b"foob".to_vec()));
+        assert_eq!(super::decode_secret("MZXW1"), None);
+    }
+}
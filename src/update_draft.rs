@@ -0,0 +1,75 @@
+// Copyright 2019 Fredrik Portström <https://portstrom.com>
+// This is free software distributed under the terms specified in
+// the file LICENSE at the top-level directory of this distribution.
+
+use super::{
+    create_draft::DraftData,
+    id::{FileId, MailId},
+    Error,
+};
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Request<'a> {
+    #[serde(rename = "_format", with = "super::protocol::format")]
+    format: (),
+    draft: &'a MailId,
+    draft_data: DraftData<'a>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(with = "super::protocol::format")]
+    _format: (),
+    attachments: Vec<FileId>,
+}
+
+/// Replaces the contents of a draft, returning the identifiers of its attachments.
+///
+/// The encrypted values of the draft data must be encrypted with the existing session key of the draft, which can be obtained with `Mail::session_key`.
+pub fn update_draft<C: 'static + hyper::client::connect::Connect>(
+    client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
+    access_token: &str,
+    draft: &MailId,
+    draft_data: DraftData,
+) -> impl futures::Future<Error = Error, Item = Vec<FileId>> {
+    let url = format!("{}/rest/tutanota/draftservice", config.origin);
+    let request = serde_json::to_string(&Request {
+        format: (),
+        draft,
+        draft_data,
+    })
+    .map_err(Error::Serialization)
+    .and_then(|request_body| {
+        super::protocol::request(
+            hyper::Method::PUT,
+            &url,
+            Some(access_token),
+            request_body.into(),
+        )
+    });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+        .and_then(|response| {
+            if match response.headers().get(hyper::header::CONTENT_TYPE) {
+                None => true,
+                Some(value) => value.as_bytes() != b"application/json;charset=utf-8",
+            } {
+                Either::A(future::err(Error::ContentType(response)))
+            } else {
+                Either::B(response.into_body().concat2().then(|result| match result {
+                    Err(error) => Err(Error::Network(error)),
+                    Ok(response_body) => match serde_json::from_slice::<Response>(&response_body) {
+                        Err(error) => Err(Error::Format(error)),
+                        Ok(response_data) => Ok(response_data.attachments),
+                    },
+                }))
+            }
+        })
+}
@@ -5,33 +5,27 @@
 use super::Error;
 use futures::Future;
 
+/// Stores changes to a folder.
+///
+/// Changing `parent_folder` doesn't move the folder. The folder stays in the list of sub folders of its original parent, which is part of its identifier, so it would still be found under the original parent. Use `move_mail_folder::move_mail_folder` to move a folder.
 pub fn update_mail_folder<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
     folder: &super::mailfolder::Folder,
 ) -> impl futures::Future<Error = Error, Item = ()> {
-    let request_body = serde_json::to_string(&folder).unwrap();
-    let mut request = hyper::Request::new(hyper::Body::from(request_body));
-    *request.method_mut() = hyper::Method::PUT;
-    // XXX Don't unwrap, but gracefully return error.
-    request.headers_mut().insert(
-        "accessToken",
-        hyper::header::HeaderValue::from_str(access_token).unwrap(),
-    );
-    let url = format!(
-        "https://mail.tutanota.com/rest/tutanota/mailfolder/{}/{}",
-        folder.id.0, folder.id.1
-    );
-    // XXX Don't unwrap, but gracefully return error.
-    *request.uri_mut() = url.parse().unwrap();
-    client.request(request).then(|result| match result {
-        Err(error) => Err(Error::Network(error)),
-        Ok(response) => {
-            if response.status() == hyper::StatusCode::OK {
-                Ok(())
-            } else {
-                Err(Error::Status(response))
-            }
-        }
-    })
+    let url = format!("{}/rest/tutanota/mailfolder/{}", config.origin, folder.id);
+    let request = serde_json::to_string(&folder)
+        .map_err(Error::Serialization)
+        .and_then(|request_body| {
+            super::protocol::request(
+                hyper::Method::PUT,
+                &url,
+                Some(access_token),
+                request_body.into(),
+            )
+        });
+    super::protocol::send(client, request)
+        .and_then(|response| super::protocol::check_status(response, hyper::StatusCode::OK))
+        .map(|_| ())
 }
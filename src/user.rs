@@ -2,15 +2,33 @@
 // This is free software distributed under the terms specified in
 // the file LICENSE at the top-level directory of this distribution.
 
-use super::Error;
+use super::{
+    id::{GroupId, UserId},
+    Error,
+};
 use futures::Future;
 use serde_derive::Deserialize;
 
+string_enum! {
+    /// The kind of a group a user is a member of.
+    GroupType {
+        User = "0",
+        Admin = "1",
+        MailingList = "2",
+        Customer = "3",
+        External = "4",
+        Mail = "5",
+        Contact = "6",
+        File = "7",
+        LocalAdmin = "8",
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Membership {
-    pub group: String,
-    pub group_type: String,
+    pub group: GroupId,
+    pub group_type: GroupType,
     #[serde(with = "super::protocol::base64")]
     pub sym_enc_g_key: Vec<u8>,
 }
@@ -33,10 +51,11 @@ pub struct UserGroup {
 
 pub fn fetch_user<C: 'static + hyper::client::connect::Connect>(
     client: &hyper::Client<C, hyper::Body>,
+    config: &super::Config,
     access_token: &str,
-    user: &str,
+    user: &UserId,
 ) -> impl futures::Future<Error = Error, Item = Response> {
-    let url = format!("https://mail.tutanota.com/rest/sys/user/{}", user);
+    let url = format!("{}/rest/sys/user/{}", config.origin, user);
     super::authenticated_get::get(client, access_token, &url).and_then(|response_body| {
         serde_json::from_slice::<Response>(&response_body).map_err(Error::Format)
     })